@@ -0,0 +1,230 @@
+//! Please see the struct level documentation.
+
+use owning_ref::OwningRef;
+use parking_lot::{RwLock, RwLockReadGuard};
+use std::collections::BTreeMap;
+use std::ops::{Bound, Deref, RangeBounds};
+
+/// ConcurrentBTreeMap is a threadsafe ordered map supporting range queries.
+///
+/// Unlike the hash based maps in this crate it is sharded by key range. The shards are defined by a sorted
+/// list of boundaries given on creation, shard `i` holds the keys between boundary `i - 1` inclusive and
+/// boundary `i` exclusive. Good boundaries spread the keys evenly since every shard is locked as a whole.
+///
+/// Range queries only lock the shards overlapping the range.
+pub struct ConcurrentBTreeMap<K, V>
+where
+    K: Ord,
+{
+    boundaries: Box<[K]>,
+    shards: Box<[RwLock<BTreeMap<K, V>>]>,
+}
+
+impl<'a, K: 'a, V: 'a> ConcurrentBTreeMap<K, V>
+where
+    K: Ord,
+{
+    /// Create a new map with a single shard.
+    pub fn new() -> Self {
+        Self::with_boundaries(Vec::new())
+    }
+
+    /// Create a new map with one shard more than the amount of boundaries.
+    ///
+    /// Will panic if the boundaries are not sorted and unique.
+    pub fn with_boundaries(boundaries: Vec<K>) -> Self {
+        assert!(
+            boundaries.windows(2).all(|w| w[0] < w[1]),
+            "boundaries must be sorted and unique"
+        );
+
+        Self {
+            shards: (0..=boundaries.len())
+                .map(|_| RwLock::new(BTreeMap::new()))
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
+            boundaries: boundaries.into_boxed_slice(),
+        }
+    }
+
+    /// Insert an element into the map. Returns the previous value if one existed.
+    #[inline]
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.shards[self.determine_shard(&key)]
+            .write()
+            .insert(key, value)
+    }
+
+    /// Get a shared reference to an element contained within the map.
+    #[inline]
+    pub fn get(&'a self, key: &K) -> Option<BTreeMapRef<'a, K, V>> {
+        let shard = self.shards[self.determine_shard(key)].read();
+        OwningRef::new(shard)
+            .try_map(|v| v.get(key).ok_or(()))
+            .ok()
+            .map(|ptr| BTreeMapRef { ptr })
+    }
+
+    /// Remove an element from the map if it exists.
+    #[inline]
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.shards[self.determine_shard(key)].write().remove(key)
+    }
+
+    /// Check if the map contains the specified key.
+    #[inline]
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.shards[self.determine_shard(key)]
+            .read()
+            .contains_key(key)
+    }
+
+    /// Clone all (K, V) pairs within a range in key order.
+    ///
+    /// The overlapping shards are read locked in order and held together so the result is consistent.
+    /// Unlike `BTreeMap::range` a range with its start past its end does not panic but is empty.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        match (range.start_bound(), range.end_bound()) {
+            (Bound::Included(start), Bound::Included(end)) if start > end => return Vec::new(),
+            (Bound::Included(start), Bound::Excluded(end))
+            | (Bound::Excluded(start), Bound::Included(end))
+            | (Bound::Excluded(start), Bound::Excluded(end))
+                if start >= end =>
+            {
+                return Vec::new()
+            }
+            _ => (),
+        }
+
+        let first = match range.start_bound() {
+            Bound::Included(key) | Bound::Excluded(key) => self.determine_shard(key),
+            Bound::Unbounded => 0,
+        };
+
+        let last = match range.end_bound() {
+            Bound::Included(key) | Bound::Excluded(key) => self.determine_shard(key),
+            Bound::Unbounded => self.shards.len() - 1,
+        };
+
+        if first > last {
+            return Vec::new();
+        }
+
+        let shards: Vec<_> = self.shards[first..=last].iter().map(|s| s.read()).collect();
+
+        shards
+            .iter()
+            .flat_map(|shard| shard.range((range.start_bound(), range.end_bound())))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    /// Get the amount of elements stored within the map.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|s| s.read().len()).sum()
+    }
+
+    /// Check if the map is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    fn determine_shard(&self, key: &K) -> usize {
+        self.boundaries.partition_point(|boundary| boundary <= key)
+    }
+}
+
+impl<K, V> Default for ConcurrentBTreeMap<K, V>
+where
+    K: Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A shared reference into a ConcurrentBTreeMap.
+pub struct BTreeMapRef<'a, K, V> {
+    ptr: OwningRef<RwLockReadGuard<'a, BTreeMap<K, V>>, V>,
+}
+
+impl<'a, K, V> Deref for BTreeMapRef<'a, K, V> {
+    type Target = V;
+
+    #[inline]
+    fn deref(&self) -> &V {
+        &self.ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{assert_send_sync, assert_sync};
+    use rayon::prelude::*;
+
+    #[test]
+    fn range_in_order() {
+        let map = ConcurrentBTreeMap::with_boundaries(vec![250, 500, 750]);
+
+        (0..1000_i32).into_par_iter().for_each(|i| {
+            map.insert(i, i * 2);
+        });
+
+        assert_eq!(map.len(), 1000);
+        assert_eq!(
+            map.range(100..200),
+            (100..200).map(|i| (i, i * 2)).collect::<Vec<_>>()
+        );
+        assert_eq!(map.range(240..=260).len(), 21);
+        assert_eq!(map.range(990..).len(), 10);
+        assert!(map.range(2000..3000).is_empty());
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn range_reversed_is_empty() {
+        let map = ConcurrentBTreeMap::with_boundaries(vec![250, 500, 750]);
+
+        for i in 0..1000_i32 {
+            map.insert(i, i);
+        }
+
+        assert!(map.range(200..100).is_empty());
+        assert!(map.range(600..100).is_empty());
+        assert!(map.range(200..=100).is_empty());
+        assert!(map.range(100..100).is_empty());
+        assert!(map
+            .range((Bound::Excluded(100), Bound::Excluded(100)))
+            .is_empty());
+        assert!(map
+            .range((Bound::Excluded(100), Bound::Included(100)))
+            .is_empty());
+        assert_eq!(map.range(100..=100), vec![(100, 100)]);
+    }
+
+    #[test]
+    fn insert_get_remove() {
+        let map = ConcurrentBTreeMap::new();
+        assert_eq!(map.insert("foo", 1_i32), None);
+        assert_eq!(map.insert("foo", 2), Some(1));
+        assert_eq!(*map.get(&"foo").unwrap(), 2);
+        assert_eq!(map.remove(&"foo"), Some(2));
+        assert!(!map.contains_key(&"foo"));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn send_sync() {
+        assert_send_sync::<ConcurrentBTreeMap<String, Vec<u8>>>();
+        // BTreeMapRef holds a lock guard which must be released on the thread that acquired it.
+        assert_sync::<BTreeMapRef<String, Vec<u8>>>();
+    }
+}
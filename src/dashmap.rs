@@ -1,20 +1,33 @@
 //! Please see the struct level documentation.
 
-use crate::fut_rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use crate::fut_rwlock::{RwLock, RwLockReadGuard, RwLockUpgradableReadGuard, RwLockWriteGuard};
 use crate::util;
 use crate::util::map_in_place;
 use futures::future::{Future, FutureExt};
-use hashbrown::HashMap;
+use hashbrown::hash_map::{Entry, RawEntryMut};
+use hashbrown::{HashMap, HashSet};
 use owning_ref::{OwningRef, OwningRefMut};
+use parking_lot::{Condvar, Mutex};
+#[cfg(feature = "rayon")]
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+#[cfg(feature = "serde")]
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+#[cfg(feature = "serde")]
+use serde::ser::{Serialize, SerializeMap, Serializer};
 use std::borrow::Borrow;
 use std::convert::TryInto;
-use std::hash::Hash;
-use std::hash::Hasher;
+#[cfg(feature = "serde")]
+use std::fmt;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
 use std::marker::PhantomData;
-use std::ops::{Deref, DerefMut};
+use std::ops::{AddAssign, Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
+/// The default hasher of DashMap, based on seahash.
+pub type SeaBuildHasher = BuildHasherDefault<seahash::SeaHasher>;
+
 /// DashMap is a threadsafe, versatile and concurrent hashmap with good performance and is balanced for both reads and writes.
 ///
 /// The API mostly matches that of the standard library hashmap but there are some
@@ -35,17 +48,22 @@ use std::time::Duration;
 ///
 /// You should not rely on being able to hold any combination of references involving a mutable one as it may cause a deadlock.
 /// This will be fixed in the future.
+///
+/// The map and its references are Send and Sync when both K and V are Send and Sync.
 #[deprecated(note = "DashMap from ccl is deprecated and kept for compat. Consider importing it from the dashmap crate.")]
-pub struct DashMap<K, V>
+pub struct DashMap<K, V, S = SeaBuildHasher>
 where
     K: Hash + Eq,
 {
     ncb: usize,
-    submaps: Box<[RwLock<HashMap<K, V>>]>,
+    submaps: Box<[RwLock<HashMap<K, V, S>>]>,
     hash_nonce: usize,
+    len: AtomicUsize,
+    in_flight: InFlight,
+    hash_builder: S,
 }
 
-impl<'a, K: 'a, V: 'a> DashMap<K, V>
+impl<K, V> DashMap<K, V>
 where
     K: Hash + Eq,
 {
@@ -56,46 +74,187 @@ where
     ///
     /// Will panic if the first parameter plugged into the formula 2^n produces a result higher than isize::MAX.
     pub fn new(num_chunks_log_2: u8) -> Self {
-        let ncm = 1 << num_chunks_log_2 as usize;
+        Self::with_hasher(num_chunks_log_2, SeaBuildHasher::default())
+    }
 
-        Self {
-            ncb: num_chunks_log_2 as usize,
-            submaps: (0..ncm)
-                .map(|_| RwLock::new(HashMap::new()))
-                .collect::<Vec<_>>()
-                .into_boxed_slice(),
-            hash_nonce: rand::random(),
-        }
+    /// Create a new DashMap with at least the given amount of chunks.
+    /// The amount is rounded up to the next power of two, which is what `new` takes the exponent of.
+    ///
+    /// Will panic if the amount is zero.
+    pub fn with_shard_amount(shard_amount: usize) -> Self {
+        assert!(shard_amount > 0, "shard amount must be non-zero");
+
+        Self::new(shard_amount.next_power_of_two().trailing_zeros() as u8)
     }
 
     /// Create a new DashMap with a specified capacity.
     ///
+    /// The capacity is split evenly between the chunks and rounded up, so every chunk reserves room for at least
+    /// one element when a non-zero capacity is smaller than the amount of chunks.
+    /// Since keys are spread by hash a chunk may still have to grow before the total capacity is reached.
+    ///
     /// Will panic if the first parameter plugged into the formula 2^n produces a result higher than isize::MAX.
     pub fn with_capacity(num_chunks_log_2: u8, capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(num_chunks_log_2, capacity, SeaBuildHasher::default())
+    }
+
+    /// Create a new DashMap from a parallel iterator. Elements are inserted concurrently from the rayon worker threads.
+    ///
+    /// The amount of chunks is based on the rayon thread count instead of the cpu count.
+    /// Workers only block each other when inserting into the same chunk at the same time, which becomes rare
+    /// with 8 chunks per thread unless the keys are heavily skewed.
+    #[cfg(feature = "rayon")]
+    pub fn par_from_iter<I: IntoParallelIterator<Item = (K, V)>>(iter: I) -> Self
+    where
+        K: Send + Sync,
+        V: Send + Sync,
+    {
+        let chunks = (rayon::current_num_threads() * 8).next_power_of_two();
+        let map = Self::new(chunks.trailing_zeros() as u8);
+
+        iter.into_par_iter().for_each(|(k, v)| {
+            map.insert(k, v);
+        });
+
+        map
+    }
+}
+
+impl<'a, K: 'a, V: 'a, S: 'a> DashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Clone,
+{
+    /// Create a new DashMap using the given hasher for choosing chunks and within the chunks.
+    ///
+    /// Chunks are still chosen with a random nonce mixed into the hash, so a deterministic hasher
+    /// does not make the chunk of a key predictable.
+    ///
+    /// Will panic if the first parameter plugged into the formula 2^n produces a result higher than isize::MAX.
+    pub fn with_hasher(num_chunks_log_2: u8, hasher: S) -> Self {
+        Self::with_capacity_and_hasher(num_chunks_log_2, 0, hasher)
+    }
+
+    /// Create a new DashMap with a specified capacity using the given hasher.
+    /// The capacity is split between the chunks the same way as in `with_capacity`.
+    ///
+    /// Will panic if the first parameter plugged into the formula 2^n produces a result higher than isize::MAX.
+    pub fn with_capacity_and_hasher(num_chunks_log_2: u8, capacity: usize, hasher: S) -> Self {
         let ncm = 1 << num_chunks_log_2 as usize;
-        let cpm = capacity / ncm;
+        let cpm = capacity.div_ceil(ncm);
 
         Self {
             ncb: num_chunks_log_2 as usize,
             submaps: (0..ncm)
-                .map(|_| RwLock::new(HashMap::with_capacity(cpm)))
+                .map(|_| RwLock::new(HashMap::with_capacity_and_hasher(cpm, hasher.clone())))
                 .collect::<Vec<_>>()
                 .into_boxed_slice(),
             hash_nonce: rand::random(),
+            len: AtomicUsize::new(0),
+            in_flight: InFlight::default(),
+            hash_builder: hasher,
         }
     }
 
-    /// Insert an element into the map.
+    /// Insert an element into the map. Returns the previous value if one existed.
     #[inline]
-    pub fn insert(&self, key: K, value: V) {
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
         let mapi = self.determine_map(&key);
         let mut submap = unsafe { self.submaps.get_unchecked(mapi).write() };
-        submap.insert(key, value);
+        let old = submap.insert(key, value);
+
+        if old.is_none() {
+            self.len.fetch_add(1, Ordering::Relaxed);
+        }
+
+        old
+    }
+
+    /// Insert an element into the map and get a unique reference to the stored value.
+    /// The returned bool is `true` if the key was not present before.
+    #[inline]
+    pub fn insert_full(&'a self, key: K, value: V) -> (bool, DashMapRefMut<'a, K, V, S>) {
+        let mapi = self.determine_map(&key);
+        let submap = unsafe { self.submaps.get_unchecked(mapi).write() };
+        let mut new = false;
+        let mut ptr_k: *const K = std::ptr::null();
+
+        let or = OwningRefMut::new(submap).map_mut(|v| {
+            let (k, v) = match v.raw_entry_mut().from_key(&key) {
+                RawEntryMut::Occupied(entry) => {
+                    let (k, v) = entry.into_key_value();
+                    *v = value;
+                    (k, v)
+                }
+                RawEntryMut::Vacant(entry) => {
+                    new = true;
+                    self.len.fetch_add(1, Ordering::Relaxed);
+                    entry.insert(key, value)
+                }
+            };
+
+            ptr_k = k;
+            v
+        });
+
+        let ptr_k = unsafe { &*ptr_k };
+        (new, DashMapRefMut { ptr_k, ptr: or })
+    }
+
+    /// Insert an element into the map and get a unique reference to the stored value.
+    /// The reference holds the write lock taken for the insert, so no other thread can observe the value in between.
+    #[inline]
+    pub fn insert_and_get_mut(&'a self, key: K, value: V) -> DashMapRefMut<'a, K, V, S> {
+        self.insert_full(key, value).1
+    }
+
+    /// Insert a batch of elements, merging an element into the existing value if the key is already present.
+    ///
+    /// Elements are grouped by chunk so every chunk is write locked once. Elements with the same key are merged
+    /// in the order they appear in the batch.
+    pub fn upsert_batch<I: IntoIterator<Item = (K, V)>, F: Fn(&mut V, V)>(
+        &self,
+        items: I,
+        merge: F,
+    ) {
+        let mut items: Vec<(usize, K, V)> = items
+            .into_iter()
+            .map(|(k, v)| (self.determine_map(&k), k, v))
+            .collect();
+        items.sort_by_key(|item| item.0);
+
+        let mut items = items.into_iter().peekable();
+
+        while let Some((mapi, k, v)) = items.next() {
+            let mut submap = unsafe { self.submaps.get_unchecked(mapi).write() };
+            let mut next = Some((k, v));
+
+            while let Some((k, v)) = next {
+                match submap.entry(k) {
+                    Entry::Occupied(mut entry) => merge(entry.get_mut(), v),
+                    Entry::Vacant(entry) => {
+                        entry.insert(v);
+                        self.len.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+
+                next = items.next_if(|item| item.0 == mapi).map(|(_, k, v)| (k, v));
+            }
+        }
+    }
+
+    /// Insert a batch of elements, replacing the values of keys that are already present.
+    ///
+    /// Elements are grouped by chunk so every chunk is write locked once. If a key appears multiple times
+    /// in the batch the last value wins.
+    #[inline]
+    pub fn insert_many<I: IntoIterator<Item = (K, V)>>(&self, items: I) {
+        self.upsert_batch(items, |old, new| *old = new);
     }
 
     /// Get or insert an element into the map if one does not exist.
     #[inline]
-    pub fn get_or_insert(&'a self, key: &K, default: V) -> DashMapRefAny<'a, K, V>
+    pub fn get_or_insert(&'a self, key: &K, default: V) -> DashMapRefAny<'a, K, V, S>
     where
         K: Clone,
     {
@@ -104,19 +263,16 @@ where
         let mapi = self.determine_map(key);
         {
             let submap = unsafe { self.submaps.get_unchecked(mapi).read() };
-            if submap.contains_key(key) {
-                let or = OwningRef::new(submap);
-                let or = or.map(|v| v.get(key).unwrap());
-                return DashMapRefAny::Shared(DashMapRef { ptr: or });
+            if let Some(r) = DashMapRef::new(submap, key) {
+                return DashMapRefAny::Shared(r);
             }
         }
         let mut submap = unsafe { self.submaps.get_unchecked(mapi).write() };
         if !submap.contains_key(key) {
             submap.insert(key.clone(), default);
+            self.len.fetch_add(1, Ordering::Relaxed);
         }
-        let or = OwningRefMut::new(submap);
-        let or = or.map_mut(|v| v.get_mut(key).unwrap());
-        DashMapRefAny::Unique(DashMapRefMut { ptr: or })
+        DashMapRefAny::Unique(DashMapRefMut::new(submap, key).unwrap())
     }
 
     /// Get or insert an element into the map if one does not exist.
@@ -125,26 +281,111 @@ where
         &'a self,
         key: &K,
         default: F,
-    ) -> DashMapRefAny<'a, K, V>
+    ) -> DashMapRefAny<'a, K, V, S>
     where
         K: Clone,
     {
         let mapi = self.determine_map(key);
         {
             let submap = unsafe { self.submaps.get_unchecked(mapi).read() };
-            if submap.contains_key(key) {
-                let or = OwningRef::new(submap);
-                let or = or.map(|v| v.get(key).unwrap());
-                return DashMapRefAny::Shared(DashMapRef { ptr: or });
+            if let Some(r) = DashMapRef::new(submap, key) {
+                return DashMapRefAny::Shared(r);
             }
         }
         let mut submap = unsafe { self.submaps.get_unchecked(mapi).write() };
         if !submap.contains_key(key) {
             submap.insert(key.clone(), default());
+            self.len.fetch_add(1, Ordering::Relaxed);
+        }
+        DashMapRefAny::Unique(DashMapRefMut::new(submap, key).unwrap())
+    }
+
+    /// Get or insert an element into the map if one does not exist.
+    /// Always returns a unique reference together with whether the element was inserted.
+    #[inline]
+    pub fn get_or_insert_entry(
+        &'a self,
+        key: &K,
+        default: V,
+    ) -> (Insertion, DashMapRefMut<'a, K, V, S>)
+    where
+        K: Clone,
+    {
+        let mapi = self.determine_map(key);
+        let submap = unsafe { self.submaps.get_unchecked(mapi).write() };
+        let mut insertion = Insertion::Existed;
+        let mut ptr_k: *const K = std::ptr::null();
+
+        let or = OwningRefMut::new(submap).map_mut(|v| {
+            let (k, v) = match v.raw_entry_mut().from_key(key) {
+                RawEntryMut::Occupied(entry) => entry.into_key_value(),
+                RawEntryMut::Vacant(entry) => {
+                    insertion = Insertion::Created;
+                    self.len.fetch_add(1, Ordering::Relaxed);
+                    entry.insert(key.clone(), default)
+                }
+            };
+
+            ptr_k = k;
+            v
+        });
+
+        let ptr_k = unsafe { &*ptr_k };
+        (insertion, DashMapRefMut { ptr_k, ptr: or })
+    }
+
+    /// Get a shared reference to an element or compute and insert it if it does not exist.
+    ///
+    /// Concurrent callers missing on the same key share a single computation, the others block until
+    /// the value has been inserted. The chunk is not locked while computing so other keys stay accessible.
+    /// If the computation panics a waiting caller computes the value instead.
+    pub fn get_or_compute<F: FnOnce() -> V>(&'a self, key: &K, f: F) -> DashMapRef<'a, K, V, S>
+    where
+        K: Clone,
+    {
+        let hash = self.hash_key(key);
+
+        loop {
+            if let Some(r) = self.get(key) {
+                return r;
+            }
+
+            let mut computing = self.in_flight.keys.lock();
+
+            if computing.contains(&hash) {
+                self.in_flight.done.wait(&mut computing);
+                continue;
+            }
+
+            computing.insert(hash);
+            drop(computing);
+
+            // Chunks are never locked while the in flight mutex is held,
+            // so the flight may be released while the returned reference keeps its chunk locked.
+            let flight = Flight {
+                in_flight: &self.in_flight,
+                hash,
+            };
+
+            // The computation may have finished between the lookup and claiming the flight.
+            if self.contains_key(key) {
+                continue;
+            }
+
+            let value = f();
+            let mapi = self.determine_map(key);
+            let mut submap = unsafe { self.submaps.get_unchecked(mapi).write() };
+
+            if !submap.contains_key(key) {
+                submap.insert(key.clone(), value);
+                self.len.fetch_add(1, Ordering::Relaxed);
+            }
+
+            let submap = RwLockWriteGuard::downgrade(submap);
+            let r = DashMapRef::new(submap, key).unwrap();
+            drop(flight);
+            return r;
         }
-        let or = OwningRefMut::new(submap);
-        let or = or.map_mut(|v| v.get_mut(key).unwrap());
-        DashMapRefAny::Unique(DashMapRefMut { ptr: or })
     }
 
     /// Check if the map contains the specified key.
@@ -159,79 +400,163 @@ where
         submap.contains_key(&key)
     }
 
+    /// Check if the map contains each of the specified keys.
+    /// Keys are grouped by chunk so that every chunk is only locked once.
+    #[inline]
+    pub fn contains_keys<Q>(&self, keys: &[Q]) -> Vec<bool>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let mut order: Vec<(usize, usize)> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (self.determine_map(key), i))
+            .collect();
+        order.sort_unstable();
+
+        let mut result = vec![false; keys.len()];
+
+        for group in order.chunk_by(|a, b| a.0 == b.0) {
+            let submap = unsafe { self.submaps.get_unchecked(group[0].0).read() };
+
+            for &(_, i) in group {
+                result[i] = submap.contains_key(&keys[i]);
+            }
+        }
+
+        result
+    }
+
+    /// Get shared references to multiple elements, in the order of the keys.
+    ///
+    /// Keys are grouped by chunk and every chunk holding one of them is read locked once, in index order.
+    /// References into the same chunk share its guard, which is released once all of them have been dropped.
+    pub fn get_many<Q>(&'a self, keys: &[&Q]) -> Vec<Option<DashMapIterRef<'a, K, V, S>>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut order: Vec<(usize, usize)> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (self.determine_map(*key), i))
+            .collect();
+        order.sort_unstable();
+
+        let mut result: Vec<Option<DashMapIterRef<'a, K, V, S>>> =
+            (0..keys.len()).map(|_| None).collect();
+
+        for group in order.chunk_by(|a, b| a.0 == b.0) {
+            let guard = Arc::new(unsafe { self.submaps.get_unchecked(group[0].0).read() });
+
+            for &(_, i) in group {
+                if let Some((k, v)) = guard.get_key_value(keys[i]) {
+                    result[i] = Some(DashMapIterRef {
+                        guard: Some(guard.clone()),
+                        ptr_k: unsafe { &*(k as *const _) },
+                        ptr_v: unsafe { &*(v as *const _) },
+                    });
+                }
+            }
+        }
+
+        result
+    }
+
     #[inline]
-    pub fn get_raw_from_key<Q>(&'a self, key: &Q) -> RwLockReadGuard<'a, HashMap<K, V>>
+    pub fn get_raw_from_key<Q>(&'a self, key: &Q) -> RwLockReadGuard<'a, HashMap<K, V, S>>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
-        let mapi = self.determine_map(key);
-        unsafe { self.submaps.get_unchecked(mapi).read() }
+        self.shard_read(self.determine_map(key))
     }
 
+    /// Get the write locked chunk a key belongs to.
+    /// Elements added or removed through it are accounted for in `len` once it is dropped.
     #[inline]
-    pub fn get_raw_mut_from_key<Q>(&'a self, key: &Q) -> RwLockWriteGuard<'a, HashMap<K, V>>
+    pub fn get_raw_mut_from_key<Q>(&'a self, key: &Q) -> DashMapRawMut<'a, K, V, S>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
-        let mapi = self.determine_map(key);
-        unsafe { self.submaps.get_unchecked(mapi).write() }
+        self.shard_write(self.determine_map(key))
+    }
+
+    /// Get the index of the chunk a key belongs to, to be used with `shard_read` and `shard_write`.
+    #[inline]
+    pub fn shard_for_key<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.determine_map(key)
+    }
+
+    /// Read lock a chunk by index.
+    ///
+    /// Holding multiple chunk locks at once can deadlock with other threads doing the same unless every thread
+    /// locks chunks in ascending index order. This includes the locks taken by the other methods of the map
+    /// while a chunk lock is held.
+    ///
+    /// Will panic if the index is not lower than `chunks_count`.
+    #[inline]
+    pub fn shard_read(&'a self, index: usize) -> RwLockReadGuard<'a, HashMap<K, V, S>> {
+        self.submaps[index].read()
+    }
+
+    /// Write lock a chunk by index.
+    /// Elements added or removed through it are accounted for in `len` once it is dropped.
+    ///
+    /// The same deadlock rules as for `shard_read` apply.
+    ///
+    /// Will panic if the index is not lower than `chunks_count`.
+    #[inline]
+    pub fn shard_write(&'a self, index: usize) -> DashMapRawMut<'a, K, V, S> {
+        let inner = self.submaps[index].write();
+
+        DashMapRawMut {
+            len_before: inner.len(),
+            inner,
+            len: &self.len,
+        }
     }
 
     /// Get a shared reference to an element contained within the map.
     #[inline]
-    pub fn get<Q>(&'a self, key: &Q) -> Option<DashMapRef<'a, K, V>>
+    pub fn get<Q>(&'a self, key: &Q) -> Option<DashMapRef<'a, K, V, S>>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
         let mapi = self.determine_map(key);
         let submap = unsafe { self.submaps.get_unchecked(mapi).read() };
-        if submap.contains_key(&key) {
-            let or = OwningRef::new(submap);
-            let or = or.map(|v| v.get(key).unwrap());
-            Some(DashMapRef { ptr: or })
-        } else {
-            None
-        }
+        DashMapRef::new(submap, key)
     }
 
+    /// Get a shared reference to an element, waiting for the chunk lock asynchronously instead of blocking the thread.
     #[inline]
-    pub fn async_get<Q>(&'a self, key: Q) -> impl Future<Output = Option<DashMapRef<'a, K, V>>>
+    pub fn async_get<Q>(&'a self, key: Q) -> impl Future<Output = Option<DashMapRef<'a, K, V, S>>>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + Sized,
     {
         let mapi = self.determine_map(&key);
         let submapfut = unsafe { self.submaps.get_unchecked(mapi).async_read() };
-        submapfut.map(move |submap| {
-            if submap.contains_key(&key) {
-                let or = OwningRef::new(submap);
-                let or = or.map(|v| v.get(&key).unwrap());
-                Some(DashMapRef { ptr: or })
-            } else {
-                None
-            }
-        })
+        submapfut.map(move |submap| DashMapRef::new(submap, &key))
     }
 
     /// Same as above but will return an error if the method would block at the current time.
     #[inline]
-    pub fn try_get<Q>(&'a self, key: &Q) -> TryGetResult<DashMapRef<'a, K, V>>
+    pub fn try_get<Q>(&'a self, key: &Q) -> TryGetResult<DashMapRef<'a, K, V, S>>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
         let mapi = self.determine_map(&key);
         if let Some(submap) = unsafe { self.submaps.get_unchecked(mapi).try_read() } {
-            if submap.contains_key(&key) {
-                let or = OwningRef::new(submap);
-                let or = or.map(|v| v.get(key).unwrap());
-                Ok(DashMapRef { ptr: or })
-            } else {
-                Err(TryGetError::InvalidKey)
-            }
+            DashMapRef::new(submap, key).ok_or(TryGetError::InvalidKey)
         } else {
             Err(TryGetError::WouldBlock)
         }
@@ -243,28 +568,73 @@ where
         &'a self,
         key: &Q,
         timeout: Duration,
-    ) -> TryGetResult<DashMapRef<'a, K, V>>
+    ) -> TryGetResult<DashMapRef<'a, K, V, S>>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
         let mapi = self.determine_map(&key);
         if let Some(submap) = unsafe { self.submaps.get_unchecked(mapi).try_read_for(timeout) } {
-            if submap.contains_key(&key) {
-                let or = OwningRef::new(submap);
-                let or = or.map(|v| v.get(key).unwrap());
-                Ok(DashMapRef { ptr: or })
-            } else {
-                Err(TryGetError::InvalidKey)
-            }
+            DashMapRef::new(submap, key).ok_or(TryGetError::InvalidKey)
         } else {
             Err(TryGetError::DidNotResolve)
         }
     }
 
+    /// Same as `try_get` but retries with exponential backoff while the method would block.
+    /// Gives up with `WouldBlock` after `max_attempts` attempts.
+    #[inline]
+    pub fn get_backoff<Q>(
+        &'a self,
+        key: &Q,
+        max_attempts: usize,
+    ) -> TryGetResult<DashMapRef<'a, K, V, S>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut step = 0;
+
+        loop {
+            match self.try_get(key) {
+                Err(TryGetError::WouldBlock) if step + 1 < max_attempts => {
+                    util::backoff(step as u32);
+                    step += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Run a function on a shared reference to an element if it exists. The chunk is unlocked before returning,
+    /// which avoids the deadlocks that holding a reference across other map operations can cause.
+    #[inline]
+    pub fn get_with<Q, T, F: FnOnce(&V) -> T>(&self, key: &Q, f: F) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mapi = self.determine_map(key);
+        let submap = unsafe { self.submaps.get_unchecked(mapi).read() };
+        submap.get(key).map(f)
+    }
+
+    /// Run a function on a unique reference to an element if it exists. The chunk is unlocked before returning,
+    /// which avoids the deadlocks that holding a reference across other map operations can cause.
+    #[inline]
+    pub fn get_mut_with<Q, T, F: FnOnce(&mut V) -> T>(&self, key: &Q, f: F) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mapi = self.determine_map(key);
+        let mut submap = unsafe { self.submaps.get_unchecked(mapi).write() };
+        submap.get_mut(key).map(f)
+    }
+
     /// Shortcut for a get followed by an unwrap.
     #[inline]
-    pub fn index<Q>(&'a self, key: &Q) -> DashMapRef<'a, K, V>
+    pub fn index<Q>(&'a self, key: &Q) -> DashMapRef<'a, K, V, S>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
@@ -274,60 +644,41 @@ where
 
     /// Get a unique reference to an element contained within the map.
     #[inline]
-    pub fn get_mut<Q>(&'a self, key: &Q) -> Option<DashMapRefMut<'a, K, V>>
+    pub fn get_mut<Q>(&'a self, key: &Q) -> Option<DashMapRefMut<'a, K, V, S>>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
         let mapi = self.determine_map(&key);
         let submap = unsafe { self.submaps.get_unchecked(mapi).write() };
-        if submap.contains_key(&key) {
-            let or = OwningRefMut::new(submap);
-            let or = or.map_mut(|v| v.get_mut(key).unwrap());
-            Some(DashMapRefMut { ptr: or })
-        } else {
-            None
-        }
+        DashMapRefMut::new(submap, key)
     }
 
+    /// Get a unique reference to an element, waiting for the chunk lock asynchronously instead of blocking the thread.
     #[inline]
     pub fn async_get_mut<Q>(
         &'a self,
         key: Q,
-    ) -> impl Future<Output = Option<DashMapRefMut<'a, K, V>>>
+    ) -> impl Future<Output = Option<DashMapRefMut<'a, K, V, S>>>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + Sized,
     {
         let mapi = self.determine_map(&key);
         let submapfut = unsafe { self.submaps.get_unchecked(mapi).async_write() };
-        submapfut.map(move |submap| {
-            if submap.contains_key(&key) {
-                let or = OwningRefMut::new(submap);
-                let or = or.map_mut(|v| v.get_mut(&key).unwrap());
-                Some(DashMapRefMut { ptr: or })
-            } else {
-                None
-            }
-        })
+        submapfut.map(move |submap| DashMapRefMut::new(submap, &key))
     }
 
     /// Same as above but will return an error if the method would block at the current time.
     #[inline]
-    pub fn try_get_mut<Q>(&'a self, key: &Q) -> TryGetResult<DashMapRefMut<'a, K, V>>
+    pub fn try_get_mut<Q>(&'a self, key: &Q) -> TryGetResult<DashMapRefMut<'a, K, V, S>>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
         let mapi = self.determine_map(&key);
         if let Some(submap) = unsafe { self.submaps.get_unchecked(mapi).try_write() } {
-            if submap.contains_key(&key) {
-                let or = OwningRefMut::new(submap);
-                let or = or.map_mut(|v| v.get_mut(key).unwrap());
-                Ok(DashMapRefMut { ptr: or })
-            } else {
-                Err(TryGetError::InvalidKey)
-            }
+            DashMapRefMut::new(submap, key).ok_or(TryGetError::InvalidKey)
         } else {
             Err(TryGetError::WouldBlock)
         }
@@ -339,28 +690,43 @@ where
         &'a self,
         key: &Q,
         timeout: Duration,
-    ) -> TryGetResult<DashMapRefMut<'a, K, V>>
+    ) -> TryGetResult<DashMapRefMut<'a, K, V, S>>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
         let mapi = self.determine_map(&key);
         if let Some(submap) = unsafe { self.submaps.get_unchecked(mapi).try_write_for(timeout) } {
-            if submap.contains_key(&key) {
-                let or = OwningRefMut::new(submap);
-                let or = or.map_mut(|v| v.get_mut(key).unwrap());
-                Ok(DashMapRefMut { ptr: or })
-            } else {
-                Err(TryGetError::InvalidKey)
-            }
+            DashMapRefMut::new(submap, key).ok_or(TryGetError::InvalidKey)
         } else {
             Err(TryGetError::DidNotResolve)
         }
     }
 
+    /// Get an upgradable reference to an element contained within the map.
+    /// Readers may still access the shard while it is held but writers are excluded,
+    /// so the reference can later be upgraded to a unique one without releasing the lock.
+    #[inline]
+    pub fn get_upgradable<Q>(&'a self, key: &Q) -> Option<DashMapUpgradableRef<'a, K, V, S>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mapi = self.determine_map(key);
+        let submap = unsafe { self.submaps.get_unchecked(mapi).upgradable_read() };
+        if let Some((k, _)) = submap.get_key_value(key) {
+            let ptr_k = unsafe { &*(k as *const _) };
+            let or = OwningRef::new(submap);
+            let or = or.map(|v| v.get(key).unwrap());
+            Some(DashMapUpgradableRef { ptr_k, ptr: or })
+        } else {
+            None
+        }
+    }
+
     /// Shortcut for a get_mut followed by an unwrap.
     #[inline]
-    pub fn index_mut<Q>(&'a self, key: &Q) -> DashMapRefMut<'a, K, V>
+    pub fn index_mut<Q>(&'a self, key: &Q) -> DashMapRefMut<'a, K, V, S>
     where
         K: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
@@ -369,9 +735,11 @@ where
     }
 
     /// Get the amount of elements stored within the map.
+    ///
+    /// The length is tracked in a counter updated by every modification so this does not lock any chunks.
     #[inline]
     pub fn len(&self) -> usize {
-        self.submaps.iter().map(|s| s.read().len()).sum()
+        self.len.load(Ordering::Relaxed)
     }
 
     /// Check if the map is empty.
@@ -389,7 +757,13 @@ where
     {
         let mapi = self.determine_map(&key);
         let mut submap = unsafe { self.submaps.get_unchecked(mapi).write() };
-        submap.remove_entry(key)
+        let removed = submap.remove_entry(key);
+
+        if removed.is_some() {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        removed
     }
 
     /// Retain all elements that the specified function returns `true` for.
@@ -397,7 +771,10 @@ where
     pub fn retain<F: Clone + FnMut(&K, &mut V) -> bool>(&self, f: F) {
         self.submaps.iter().for_each(|locked| {
             let mut submap = locked.write();
+            let len_before = submap.len();
             submap.retain(f.clone());
+            self.len
+                .fetch_sub(len_before - submap.len(), Ordering::Relaxed);
         });
     }
 
@@ -406,299 +783,750 @@ where
     pub fn clear(&self) {
         self.submaps.iter().for_each(|locked| {
             let mut submap = locked.write();
+            self.len.fetch_sub(submap.len(), Ordering::Relaxed);
             submap.clear();
         });
     }
 
-    /// Apply a function to a a specified entry in the map.
+    /// Remove all elements from the map, yielding them one chunk at a time.
+    ///
+    /// A chunk is only write locked while it is emptied, so other chunks stay accessible and no lock is held
+    /// while the yielded elements are processed. Elements of chunks not yet reached are removed when
+    /// the iterator is dropped.
     #[inline]
-    pub fn alter<Q, F: FnOnce(V) -> V>(&self, k: &Q, f: F)
-    where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
-    {
-        let v = self.get_mut(k);
-        if let Some(mut v) = v {
-            unsafe {
-                map_in_place(&mut *v, f);
-            }
+    pub fn drain(&'a self) -> Drain<'a, K, V, S> {
+        Drain {
+            c_map_index: 0,
+            map: self,
+            c_iter: None,
         }
     }
 
-    /// Apply a function to every item in the map.
-    #[inline]
-    pub fn alter_all<F: FnMut(V) -> V + Clone>(&self, f: F) {
-        self.chunks_write().for_each(|mut t| {
-            t.iter_mut().for_each(|v| unsafe {
-                map_in_place(&mut *v.1, f.clone());
-            })
-        });
-    }
-
-    /// Iterate over the (K, V) pairs stored in the map immutably.
-    #[inline]
-    pub fn iter(&'a self) -> Iter<'a, K, V> {
-        Iter::new(self)
-    }
-
-    /// Iterate over the (K, V) pairs stored in the map mutably.
-    #[inline]
-    pub fn iter_mut(&'a self) -> IterMut<'a, K, V> {
-        IterMut::new(self)
-    }
-
-    /// Iterate over chunks in a read only fashion.
+    /// Remove all elements from the map and return them.
+    ///
+    /// Every chunk is emptied in a single pass while it is write locked. Chunks are taken one at a time
+    /// so elements inserted into already emptied chunks during the call stay in the map.
     #[inline]
-    pub fn chunks(&self) -> impl Iterator<Item = Chunk<K, V>> {
-        self.submaps.iter().map(|t| Chunk::new(t.read()))
-    }
+    pub fn snapshot_and_clear(&self) -> Vec<(K, V)> {
+        let mut entries = Vec::new();
+
+        for submap in self.submaps.iter() {
+            let mut submap = submap.write();
+            self.len.fetch_sub(submap.len(), Ordering::Relaxed);
+            let hasher = submap.hasher().clone();
+            entries.extend(std::mem::replace(
+                &mut *submap,
+                HashMap::with_hasher(hasher),
+            ));
+        }
 
-    /// Iterate over chunks in a read-write fashion.
-    #[inline]
-    pub fn chunks_write(&self) -> impl Iterator<Item = ChunkMut<K, V>> {
-        self.submaps.iter().map(|t| ChunkMut::new(t.write()))
+        entries
     }
 
-    #[inline]
-    pub(crate) fn determine_map<Q>(&self, key: &Q) -> usize
+    /// Swap the values stored under two keys. Returns whether both keys existed, nothing is changed otherwise.
+    ///
+    /// If the keys are in different chunks both are write locked in index order so concurrent swaps can't deadlock.
+    pub fn swap<Q>(&self, a: &Q, b: &Q) -> bool
     where
         K: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
-        let mut hash_state = seahash::SeaHasher::new();
-        hash_state.write_usize(self.hash_nonce);
-        key.hash(&mut hash_state);
+        if a == b {
+            return self.contains_key(a);
+        }
 
-        let hash = hash_state.finish();
-        let shift = util::ptr_size_bits() - self.ncb;
+        let mapi_a = self.determine_map(a);
+        let mapi_b = self.determine_map(b);
 
-        (hash >> shift) as usize
-    }
+        if mapi_a == mapi_b {
+            let mut submap = unsafe { self.submaps.get_unchecked(mapi_a).write() };
 
-    #[inline]
-    pub fn chunks_count(&self) -> usize {
-        self.submaps.len()
-    }
-}
+            let ptr_a = match submap.get_mut(a) {
+                Some(v) => v as *mut V,
+                None => return false,
+            };
 
-impl<K, V> Default for DashMap<K, V>
-where
-    K: Hash + Eq,
-{
-    /// Creates a new DashMap and automagically determines the optimal amount of chunks.
-    fn default() -> Self {
-        let vcount = num_cpus::get() * 8;
+            let ptr_b = match submap.get_mut(b) {
+                Some(v) => v as *mut V,
+                None => return false,
+            };
 
-        let base: usize = 2;
-        let mut p2exp: u32 = 1;
+            // The keys are different so the pointers are to different entries
+            // and the submap is not modified between getting them and swapping.
+            unsafe { std::ptr::swap(ptr_a, ptr_b) };
+            return true;
+        }
 
-        loop {
-            if vcount <= base.pow(p2exp) {
-                return Self::new(p2exp.try_into().unwrap());
-            } else {
-                p2exp += 1;
+        let (first, second) = if mapi_a < mapi_b {
+            (mapi_a, mapi_b)
+        } else {
+            (mapi_b, mapi_a)
+        };
+
+        let mut submap_first = unsafe { self.submaps.get_unchecked(first).write() };
+        let mut submap_second = unsafe { self.submaps.get_unchecked(second).write() };
+
+        let (submap_a, submap_b) = if mapi_a < mapi_b {
+            (&mut submap_first, &mut submap_second)
+        } else {
+            (&mut submap_second, &mut submap_first)
+        };
+
+        match (submap_a.get_mut(a), submap_b.get_mut(b)) {
+            (Some(va), Some(vb)) => {
+                std::mem::swap(va, vb);
+                true
             }
+            _ => false,
         }
     }
-}
 
-/// A shared reference into a DashMap created from an iterator.
-pub struct DashMapIterRef<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    guard: Option<Arc<RwLockReadGuard<'a, HashMap<K, V>>>>,
-    ptr_k: &'a K,
-    ptr_v: &'a V,
-}
+    /// Move the value stored under `old` to the key `new`, replacing any value already stored under `new`.
+    /// Returns whether `old` existed, nothing is changed otherwise.
+    ///
+    /// If the keys are in different chunks both are write locked in index order so concurrent calls can't deadlock.
+    pub fn alter_key<Q>(&self, old: &Q, new: K) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mapi_old = self.determine_map(old);
+        let mapi_new = self.determine_map::<K>(&new);
 
-impl<'a, K, V> DashMapIterRef<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    /// Get the key of the entry.
-    #[inline]
-    pub fn key(&self) -> &K {
-        self.ptr_k
+        if mapi_old == mapi_new {
+            let mut submap = unsafe { self.submaps.get_unchecked(mapi_old).write() };
+
+            let v = match submap.remove(old) {
+                Some(v) => v,
+                None => return false,
+            };
+
+            if submap.insert(new, v).is_some() {
+                self.len.fetch_sub(1, Ordering::Relaxed);
+            }
+
+            return true;
+        }
+
+        let (first, second) = if mapi_old < mapi_new {
+            (mapi_old, mapi_new)
+        } else {
+            (mapi_new, mapi_old)
+        };
+
+        let mut submap_first = unsafe { self.submaps.get_unchecked(first).write() };
+        let mut submap_second = unsafe { self.submaps.get_unchecked(second).write() };
+
+        let (submap_old, submap_new) = if mapi_old < mapi_new {
+            (&mut submap_first, &mut submap_second)
+        } else {
+            (&mut submap_second, &mut submap_first)
+        };
+
+        let v = match submap_old.remove(old) {
+            Some(v) => v,
+            None => return false,
+        };
+
+        if submap_new.insert(new, v).is_some() {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        true
     }
 
-    /// Get the value of the entry.
+    /// Apply a function to a a specified entry in the map.
     #[inline]
-    pub fn value(&self) -> &V {
-        self.ptr_v
+    pub fn alter<Q, F: FnOnce(V) -> V>(&self, k: &Q, f: F)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let v = self.get_mut(k);
+        if let Some(mut v) = v {
+            unsafe {
+                map_in_place(&mut *v, f);
+            }
+        }
     }
-}
 
-impl<'a, K, V> Drop for DashMapIterRef<'a, K, V>
-where
-    K: Hash + Eq,
-{
+    /// Replace the value of an entry with the result of a function consuming the old value.
+    /// Returns whether the key existed.
+    ///
+    /// The entry is removed and reinserted while the chunk is write locked so other threads never observe it missing.
+    /// If the function panics the entry is dropped and no longer counted by `len`.
     #[inline]
-    fn drop(&mut self) {
-        self.guard.take();
-    }
-}
+    pub fn take_replace<Q, F: FnOnce(V) -> V>(&self, key: &Q, f: F) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mapi = self.determine_map(key);
+        let mut submap = unsafe { self.submaps.get_unchecked(mapi).write() };
 
-impl<'a, K, V> Deref for DashMapIterRef<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    type Target = V;
+        match submap.remove_entry(key) {
+            Some((k, v)) => {
+                let removed = Removed { len: &self.len };
+                let v = f(v);
+                std::mem::forget(removed);
 
-    #[inline]
-    fn deref(&self) -> &V {
-        self.ptr_v
+                submap.insert(k, v);
+                true
+            }
+            None => false,
+        }
     }
-}
 
-/// An immutable iterator over a DashMap.
-#[allow(clippy::type_complexity)]
-pub struct Iter<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    c_map_index: usize,
-    map: &'a DashMap<K, V>,
-    c_iter: Option<(
-        Arc<RwLockReadGuard<'a, HashMap<K, V>>>,
-        hashbrown::hash_map::Iter<'a, K, V>,
-    )>,
-}
+    /// Add one to the value of an entry, inserting one if the key is not present. Returns the new value.
+    #[inline]
+    pub fn increment(&self, key: &K) -> V
+    where
+        K: Clone,
+        V: AddAssign + From<u8> + Clone,
+    {
+        let mapi = self.determine_map(key);
+        let mut submap = unsafe { self.submaps.get_unchecked(mapi).write() };
 
-impl<'a, K, V> Iter<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    fn new(map: &'a DashMap<K, V>) -> Self {
-        Self {
-            c_map_index: 0,
-            map,
-            c_iter: None,
+        match submap.get_mut(key) {
+            Some(v) => {
+                *v += V::from(1);
+                v.clone()
+            }
+            None => {
+                submap.insert(key.clone(), V::from(1));
+                self.len.fetch_add(1, Ordering::Relaxed);
+                V::from(1)
+            }
         }
     }
 
-    fn slow_path_new_chunk(&mut self) -> Option<DashMapIterRef<'a, K, V>> {
-        if self.c_map_index == self.map.submaps.len() {
-            return None;
+    /// Add a delta to the value of an entry, inserting the default value first if the key is not present.
+    /// The chunk is write locked once for the whole read-modify-write.
+    #[inline]
+    pub fn add_assign(&self, key: K, delta: V)
+    where
+        V: Default + AddAssign,
+    {
+        let mapi = self.determine_map(&key);
+        let mut submap = unsafe { self.submaps.get_unchecked(mapi).write() };
+
+        match submap.entry(key) {
+            Entry::Occupied(mut entry) => *entry.get_mut() += delta,
+            Entry::Vacant(entry) => {
+                *entry.insert(V::default()) += delta;
+                self.len.fetch_add(1, Ordering::Relaxed);
+            }
         }
+    }
 
-        let guard = Arc::into_raw(Arc::new(self.map.submaps[self.c_map_index].read()));
-        let iter = unsafe { (&*guard).iter() };
+    /// Create a new map with the same keys and values transformed by a function.
+    ///
+    /// The new map keeps the chunk amount, nonce and hasher so every key lands in the same chunk as in this map.
+    /// Chunks are read locked one at a time so the result is not a consistent snapshot if the map is modified concurrently.
+    pub fn map_values<W, F: Fn(&V) -> W>(&self, f: F) -> DashMap<K, W, S>
+    where
+        K: Clone,
+    {
+        let submaps = self
+            .submaps
+            .iter()
+            .map(|submap| {
+                let submap = submap.read();
+                let mut mapped =
+                    HashMap::with_capacity_and_hasher(submap.len(), self.hash_builder.clone());
+                mapped.extend(submap.iter().map(|(k, v)| (k.clone(), f(v))));
+                RwLock::new(mapped)
+            })
+            .collect::<Vec<_>>();
+        let len = submaps.iter().map(|submap| submap.read().len()).sum();
+
+        DashMap {
+            ncb: self.ncb,
+            submaps: submaps.into_boxed_slice(),
+            hash_nonce: self.hash_nonce,
+            len: AtomicUsize::new(len),
+            in_flight: InFlight::default(),
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
 
-        std::mem::replace(
-            &mut self.c_iter,
-            Some((unsafe { Arc::from_raw(guard) }, iter)),
-        );
+    /// Apply a function to every item in the map.
+    #[inline]
+    pub fn alter_all<F: FnMut(V) -> V + Clone>(&self, f: F) {
+        self.chunks_write().for_each(|mut t| {
+            t.iter_mut().for_each(|v| unsafe {
+                map_in_place(&mut *v.1, f.clone());
+            })
+        });
+    }
 
-        self.c_map_index += 1;
-        self.next()
+    /// Set every value in the map to a clone of the given value while keeping the keys.
+    #[inline]
+    pub fn reset_values(&self, value: V)
+    where
+        V: Clone,
+    {
+        self.chunks_write().for_each(|mut t| {
+            t.iter_mut().for_each(|(_, v)| *v = value.clone());
+        });
     }
-}
 
-impl<'a, K, V> Iterator for Iter<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    type Item = DashMapIterRef<'a, K, V>;
+    /// Iterate over the (K, V) pairs stored in the map immutably.
+    ///
+    /// Chunks are locked lazily one at a time as the iterator reaches them. Iterating will therefore
+    /// only block on chunks that are write locked at the time they are reached.
+    #[inline]
+    pub fn iter(&'a self) -> Iter<'a, K, V, S> {
+        Iter::new(self)
+    }
 
+    /// Iterate over the (K, V) pairs stored in the map in parallel, visiting different chunks on different threads.
+    ///
+    /// Every chunk is read locked while its pairs are being processed and until all references into it are dropped.
+    #[cfg(feature = "rayon")]
     #[inline]
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Some(c_iter) = &mut self.c_iter {
-            if let Some(i) = c_iter.1.next() {
-                let guard = Some(c_iter.0.clone());
-                let ptr_k = unsafe { &*(i.0 as *const _) };
-                let ptr_v = unsafe { &*(i.1 as *const _) };
+    pub fn par_iter(&'a self) -> impl ParallelIterator<Item = DashMapIterRef<'a, K, V, S>>
+    where
+        K: Send + Sync,
+        V: Send + Sync,
+        S: Send + Sync,
+    {
+        self.submaps.par_iter().flat_map_iter(|submap| {
+            let guard = Arc::new(submap.read());
+            let iter = unsafe { (*Arc::as_ptr(&guard)).iter() };
+
+            iter.map(move |(k, v)| DashMapIterRef {
+                guard: Some(guard.clone()),
+                ptr_k: unsafe { &*(k as *const _) },
+                ptr_v: unsafe { &*(v as *const _) },
+            })
+        })
+    }
 
-                return Some(DashMapIterRef {
-                    guard,
-                    ptr_k,
-                    ptr_v,
-                });
-            }
-        }
+    /// Apply a function to every (K, V) pair stored in the map.
+    ///
+    /// Every chunk is read locked once while its pairs are visited. Unlike `iter` no reference counted
+    /// guard has to be cloned for every element which makes this faster for large maps.
+    #[inline]
+    pub fn for_each<F: FnMut(&K, &V)>(&self, mut f: F) {
+        self.chunks()
+            .for_each(|chunk| chunk.iter().for_each(|(k, v)| f(k, v)));
+    }
 
-        self.slow_path_new_chunk()
+    /// Iterate over clones of the (K, V) pairs stored in the map.
+    ///
+    /// Every chunk is cloned into a buffer and unlocked before its pairs are yielded,
+    /// so chunks are only locked while being cloned and not while the consumer processes the pairs.
+    #[inline]
+    pub fn iter_owned(&'a self) -> IterOwned<'a, K, V, S>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        IterOwned::new(self)
     }
-}
 
-/// A shared reference into a DashMap created from an iterator.
-pub struct DashMapIterRefMut<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    guard: Option<Arc<RwLockWriteGuard<'a, HashMap<K, V>>>>,
-    ptr_k: &'a K,
-    ptr_v: &'a mut V,
-}
+    /// Iterate over the (K, V) pairs stored in the map mutably.
+    #[inline]
+    pub fn iter_mut(&'a self) -> IterMut<'a, K, V, S> {
+        IterMut::new(self)
+    }
 
-impl<'a, K, V> DashMapIterRefMut<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    /// Get the key of the entry.
+    /// Iterate over the keys stored in the map. Chunks are locked the same way as in `iter`.
     #[inline]
-    pub fn key(&self) -> &K {
-        self.ptr_k
+    pub fn keys(&'a self) -> impl Iterator<Item = DashMapKeyRef<'a, K, V, S>> {
+        self.iter().map(|inner| DashMapKeyRef { inner })
     }
 
-    /// Get the value of the entry.
+    /// Iterate over the values stored in the map. The yielded references deref to the values.
     #[inline]
-    pub fn value(&mut self) -> &mut V {
-        self.ptr_v
+    pub fn values(&'a self) -> impl Iterator<Item = DashMapIterRef<'a, K, V, S>> {
+        self.iter()
     }
-}
 
-impl<'a, K, V> Drop for DashMapIterRefMut<'a, K, V>
-where
-    K: Hash + Eq,
-{
+    /// Iterate over the values stored in the map mutably. Every chunk is write locked while its values are visited.
     #[inline]
-    fn drop(&mut self) {
-        self.guard.take();
+    pub fn values_mut(&'a self) -> impl Iterator<Item = DashMapIterRefMut<'a, K, V, S>> {
+        self.iter_mut()
     }
-}
 
-impl<'a, K, V> Deref for DashMapIterRefMut<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    type Target = V;
+    /// Iterate over chunks in a read only fashion.
+    #[inline]
+    pub fn chunks(&self) -> impl Iterator<Item = Chunk<'_, K, V, S>> {
+        self.submaps.iter().map(|t| Chunk::new(t.read()))
+    }
 
+    /// Iterate over chunks in a read-write fashion.
     #[inline]
-    fn deref(&self) -> &V {
-        self.ptr_v
+    pub fn chunks_write(&self) -> impl Iterator<Item = ChunkMut<'_, K, V, S>> {
+        self.submaps
+            .iter()
+            .map(move |t| ChunkMut::new(t.write(), &self.len))
     }
-}
 
-impl<'a, K, V> DerefMut for DashMapIterRefMut<'a, K, V>
-where
-    K: Hash + Eq,
-{
+    /// Read lock every chunk in index order and hold the locks until the returned guard is dropped.
+    /// The guard offers a view of the map that is consistent across all chunks.
+    ///
+    /// Writers to any chunk block while the guard is alive. Holding the guard while taking other
+    /// locks on the same map, or locking multiple chunks in a different order from another thread,
+    /// may deadlock.
     #[inline]
-    fn deref_mut(&mut self) -> &mut V {
-        self.ptr_v
+    pub fn lock_all_read(&'a self) -> AllShardsGuard<'a, K, V, S> {
+        AllShardsGuard {
+            chunks: self.chunks().collect(),
+        }
     }
-}
 
-/// An mutable iterator over a DashMap.
-#[allow(clippy::type_complexity)]
-pub struct IterMut<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    c_map_index: usize,
-    map: &'a DashMap<K, V>,
-    c_iter: Option<(
-        Arc<RwLockWriteGuard<'a, HashMap<K, V>>>,
-        hashbrown::hash_map::IterMut<'a, K, V>,
-    )>,
-}
+    /// Redraw the hash nonce and redistribute all entries across the chunks.
+    /// Useful if entries are unevenly spread between chunks.
+    ///
+    /// This is an expensive stop-the-world operation that rehashes every entry in the map.
+    /// It requires exclusive access since lookups in progress would otherwise use a stale nonce.
+    pub fn rebalance(&mut self) {
+        let mut entries = Vec::with_capacity(self.len());
 
-impl<'a, K, V> IterMut<'a, K, V>
+        for submap in self.submaps.iter() {
+            entries.extend(submap.write().drain());
+        }
+
+        *self.len.get_mut() = 0;
+        self.hash_nonce = rand::random();
+
+        for (k, v) in entries {
+            self.insert(k, v);
+        }
+    }
+
+    /// Collect the keys stored in a single chunk. Useful for diagnosing an overloaded chunk.
+    ///
+    /// Will panic if the index is not lower than `chunks_count`.
+    #[inline]
+    pub fn shard_keys(&self, index: usize) -> Vec<K>
+    where
+        K: Clone,
+    {
+        self.submaps[index].read().keys().cloned().collect()
+    }
+
+    #[inline]
+    fn hash_key<Q>(&self, key: &Q) -> u64
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut hash_state = self.hash_builder.build_hasher();
+        hash_state.write_usize(self.hash_nonce);
+        key.hash(&mut hash_state);
+        hash_state.finish()
+    }
+
+    #[inline]
+    pub(crate) fn determine_map<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.hash_key(key);
+        let shift = util::ptr_size_bits() - self.ncb;
+
+        // A single chunk shifts out every bit, which overflows.
+        hash.checked_shr(shift as u32).unwrap_or(0) as usize
+    }
+
+    #[inline]
+    pub fn chunks_count(&self) -> usize {
+        self.submaps.len()
+    }
+
+    /// Get a reference to the hasher of the map.
+    #[inline]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+}
+
+impl<K, V, S> DashMap<K, Arc<V>, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Clone,
+{
+    /// Get a clone of an `Arc` stored within the map. The chunk is only locked while cloning
+    /// so the returned handle can be held for as long as needed without blocking writers.
+    #[inline]
+    pub fn get_arc<Q>(&self, key: &Q) -> Option<Arc<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mapi = self.determine_map(key);
+        let submap = unsafe { self.submaps.get_unchecked(mapi).read() };
+        submap.get(key).cloned()
+    }
+}
+
+/// Compute the keys only present in `a` and the keys only present in `b`.
+///
+/// Chunks are read locked one at a time and no lock is held on both maps at once.
+pub fn diff_keys<K, V, S>(a: &DashMap<K, V, S>, b: &DashMap<K, V, S>) -> (Vec<K>, Vec<K>)
+where
+    K: Hash + Eq + Clone,
+    S: BuildHasher + Clone,
+{
+    (keys_missing_from(a, b), keys_missing_from(b, a))
+}
+
+fn keys_missing_from<K, V, S>(from: &DashMap<K, V, S>, other: &DashMap<K, V, S>) -> Vec<K>
+where
+    K: Hash + Eq + Clone,
+    S: BuildHasher + Clone,
+{
+    let mut missing = Vec::new();
+
+    for idx in 0..from.chunks_count() {
+        let keys = from.shard_keys(idx);
+        let present = other.contains_keys(&keys);
+
+        missing.extend(
+            keys.into_iter()
+                .zip(present)
+                .filter(|(_, present)| !present)
+                .map(|(k, _)| k),
+        );
+    }
+
+    missing
+}
+
+impl<K, V> Default for DashMap<K, V>
+where
+    K: Hash + Eq,
+{
+    /// Creates a new DashMap and automagically determines the optimal amount of chunks.
+    fn default() -> Self {
+        let vcount = num_cpus::get() * 8;
+
+        let base: usize = 2;
+        let mut p2exp: u32 = 1;
+
+        loop {
+            if vcount <= base.pow(p2exp) {
+                return Self::new(p2exp.try_into().unwrap());
+            } else {
+                p2exp += 1;
+            }
+        }
+    }
+}
+
+impl<K, V, S> Clone for DashMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: Clone,
+{
+    /// Clones the map chunk by chunk, read locking one chunk at a time.
+    fn clone(&self) -> Self {
+        let submaps = self
+            .submaps
+            .iter()
+            .map(|submap| RwLock::new(submap.read().clone()))
+            .collect::<Vec<_>>();
+        let len = submaps.iter().map(|submap| submap.read().len()).sum();
+
+        Self {
+            ncb: self.ncb,
+            submaps: submaps.into_boxed_slice(),
+            // The chunks are cloned as is, so the nonce has to be kept for determine_map to route keys to the chunk holding them.
+            hash_nonce: self.hash_nonce,
+            len: AtomicUsize::new(len),
+            in_flight: InFlight::default(),
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+/// Compares the contents of two maps. The maps may have different chunk amounts, every key is looked up with `get`.
+/// Chunks are read locked one at a time so the result is not a consistent snapshot if a map is modified concurrently.
+/// The pairs of a chunk are cloned before looking them up so no lock is held on both maps at once.
+impl<K, V, S> PartialEq for DashMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: PartialEq + Clone,
+    S: BuildHasher + Clone,
+{
+    fn eq(&self, other: &Self) -> bool {
+        if std::ptr::eq(self, other) {
+            return true;
+        }
+
+        if self.len() != other.len() {
+            return false;
+        }
+
+        self.submaps.iter().all(|submap| {
+            let pairs: Vec<(K, V)> = submap
+                .read()
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+
+            pairs
+                .iter()
+                .all(|(k, v)| other.get(k).is_some_and(|ov| *ov == *v))
+        })
+    }
+}
+
+/// Serializes the pairs as a map. Chunks are read locked one at a time so the output is not a consistent snapshot
+/// if the map is modified concurrently.
+#[cfg(feature = "serde")]
+impl<K, V, H> Serialize for DashMap<K, V, H>
+where
+    K: Hash + Eq + Serialize,
+    V: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+
+        for submap in self.submaps.iter() {
+            for (k, v) in submap.read().iter() {
+                map.serialize_entry(k, v)?;
+            }
+        }
+
+        map.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+struct DashMapVisitor<K, V> {
+    marker: PhantomData<fn() -> (K, V)>,
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K, V> Visitor<'de> for DashMapVisitor<K, V>
+where
+    K: Hash + Eq + Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    type Value = DashMap<K, V>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut access: M) -> Result<Self::Value, M::Error> {
+        let map = DashMap::default();
+
+        while let Some((k, v)) = access.next_entry()? {
+            map.insert(k, v);
+        }
+
+        Ok(map)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K, V> Deserialize<'de> for DashMap<K, V>
+where
+    K: Hash + Eq + Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(DashMapVisitor {
+            marker: PhantomData,
+        })
+    }
+}
+
+/// A shared reference into a DashMap created from an iterator.
+pub struct DashMapIterRef<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    guard: Option<Arc<RwLockReadGuard<'a, HashMap<K, V, S>>>>,
+    ptr_k: &'a K,
+    ptr_v: &'a V,
+}
+
+impl<'a, K, V, S> DashMapIterRef<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    /// Get the key of the entry.
+    #[inline]
+    pub fn key(&self) -> &K {
+        self.ptr_k
+    }
+
+    /// Get the value of the entry.
+    #[inline]
+    pub fn value(&self) -> &V {
+        self.ptr_v
+    }
+}
+
+impl<'a, K, V, S> Drop for DashMapIterRef<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    #[inline]
+    fn drop(&mut self) {
+        self.guard.take();
+    }
+}
+
+impl<'a, K, V, S> Deref for DashMapIterRef<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    type Target = V;
+
+    #[inline]
+    fn deref(&self) -> &V {
+        self.ptr_v
+    }
+}
+
+/// A reference to a key in a DashMap created from the keys iterator.
+pub struct DashMapKeyRef<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    inner: DashMapIterRef<'a, K, V, S>,
+}
+
+impl<'a, K, V, S> Deref for DashMapKeyRef<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    type Target = K;
+
+    #[inline]
+    fn deref(&self) -> &K {
+        self.inner.key()
+    }
+}
+
+/// An immutable iterator over a DashMap.
+#[allow(clippy::type_complexity)]
+pub struct Iter<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    c_map_index: usize,
+    map: &'a DashMap<K, V, S>,
+    c_iter: Option<(
+        Arc<RwLockReadGuard<'a, HashMap<K, V, S>>>,
+        hashbrown::hash_map::Iter<'a, K, V>,
+    )>,
+}
+
+impl<'a, K, V, S> Iter<'a, K, V, S>
 where
     K: Hash + Eq,
 {
-    fn new(map: &'a DashMap<K, V>) -> Self {
+    fn new(map: &'a DashMap<K, V, S>) -> Self {
         Self {
             c_map_index: 0,
             map,
@@ -706,250 +1534,1881 @@ where
         }
     }
 
-    fn slow_path_new_chunk(&mut self) -> Option<DashMapIterRefMut<'a, K, V>> {
-        if self.c_map_index == self.map.submaps.len() {
-            return None;
-        }
+    fn slow_path_new_chunk(&mut self) -> Option<DashMapIterRef<'a, K, V, S>> {
+        if self.c_map_index == self.map.submaps.len() {
+            return None;
+        }
+
+        let guard = Arc::into_raw(Arc::new(self.map.submaps[self.c_map_index].read()));
+        let iter = unsafe { (&*guard).iter() };
+
+        std::mem::replace(
+            &mut self.c_iter,
+            Some((unsafe { Arc::from_raw(guard) }, iter)),
+        );
+
+        self.c_map_index += 1;
+        self.next()
+    }
+}
+
+impl<'a, K, V, S> Iterator for Iter<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    type Item = DashMapIterRef<'a, K, V, S>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(c_iter) = &mut self.c_iter {
+            if let Some(i) = c_iter.1.next() {
+                let guard = Some(c_iter.0.clone());
+                let ptr_k = unsafe { &*(i.0 as *const _) };
+                let ptr_v = unsafe { &*(i.1 as *const _) };
+
+                return Some(DashMapIterRef {
+                    guard,
+                    ptr_k,
+                    ptr_v,
+                });
+            }
+        }
+
+        self.slow_path_new_chunk()
+    }
+}
+
+/// A shared reference into a DashMap created from an iterator.
+pub struct DashMapIterRefMut<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    guard: Option<Arc<RwLockWriteGuard<'a, HashMap<K, V, S>>>>,
+    ptr_k: &'a K,
+    ptr_v: &'a mut V,
+}
+
+impl<'a, K, V, S> DashMapIterRefMut<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    /// Get the key of the entry.
+    #[inline]
+    pub fn key(&self) -> &K {
+        self.ptr_k
+    }
+
+    /// Get the value of the entry.
+    #[inline]
+    pub fn value(&mut self) -> &mut V {
+        self.ptr_v
+    }
+}
+
+impl<'a, K, V, S> Drop for DashMapIterRefMut<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    #[inline]
+    fn drop(&mut self) {
+        self.guard.take();
+    }
+}
+
+impl<'a, K, V, S> Deref for DashMapIterRefMut<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    type Target = V;
+
+    #[inline]
+    fn deref(&self) -> &V {
+        self.ptr_v
+    }
+}
+
+impl<'a, K, V, S> DerefMut for DashMapIterRefMut<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut V {
+        self.ptr_v
+    }
+}
+
+/// An mutable iterator over a DashMap.
+#[allow(clippy::type_complexity)]
+pub struct IterMut<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    c_map_index: usize,
+    map: &'a DashMap<K, V, S>,
+    c_iter: Option<(
+        Arc<RwLockWriteGuard<'a, HashMap<K, V, S>>>,
+        hashbrown::hash_map::IterMut<'a, K, V>,
+    )>,
+}
+
+impl<'a, K, V, S> IterMut<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    fn new(map: &'a DashMap<K, V, S>) -> Self {
+        Self {
+            c_map_index: 0,
+            map,
+            c_iter: None,
+        }
+    }
+
+    fn slow_path_new_chunk(&mut self) -> Option<DashMapIterRefMut<'a, K, V, S>> {
+        if self.c_map_index == self.map.submaps.len() {
+            return None;
+        }
+
+        let guard: *mut RwLockWriteGuard<'_, HashMap<K, V, S>> =
+            Arc::into_raw(Arc::new(self.map.submaps[self.c_map_index].write())) as _;
+        let gr: &mut RwLockWriteGuard<'_, HashMap<K, V, S>> = unsafe { &mut *guard };
+        let iter = gr.iter_mut();
+
+        std::mem::replace(
+            &mut self.c_iter,
+            Some((unsafe { Arc::from_raw(guard) }, iter)),
+        );
+
+        self.c_map_index += 1;
+        self.next()
+    }
+}
+
+impl<'a, K, V, S> Iterator for IterMut<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    type Item = DashMapIterRefMut<'a, K, V, S>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(c_iter) = &mut self.c_iter {
+            if let Some(i) = c_iter.1.next() {
+                let guard = Some(c_iter.0.clone());
+                let ptr_k = unsafe { &*(i.0 as *const _) };
+                let ptr_v = unsafe { &mut *(i.1 as *mut _) };
+
+                return Some(DashMapIterRefMut {
+                    guard,
+                    ptr_k,
+                    ptr_v,
+                });
+            }
+        }
+
+        self.slow_path_new_chunk()
+    }
+}
+
+/// An iterator over clones of the pairs in a DashMap.
+pub struct IterOwned<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    c_map_index: usize,
+    map: &'a DashMap<K, V, S>,
+    c_buffer: std::vec::IntoIter<(K, V)>,
+}
+
+impl<'a, K, V, S> IterOwned<'a, K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    fn new(map: &'a DashMap<K, V, S>) -> Self {
+        Self {
+            c_map_index: 0,
+            map,
+            c_buffer: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<'a, K, V, S> Iterator for IterOwned<'a, K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    type Item = (K, V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(pair) = self.c_buffer.next() {
+                return Some(pair);
+            }
+
+            if self.c_map_index == self.map.submaps.len() {
+                return None;
+            }
+
+            let buffer: Vec<(K, V)> = self.map.submaps[self.c_map_index]
+                .read()
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+
+            self.c_buffer = buffer.into_iter();
+            self.c_map_index += 1;
+        }
+    }
+}
+
+/// An iterator removing the pairs from a DashMap, one chunk at a time.
+pub struct Drain<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    c_map_index: usize,
+    map: &'a DashMap<K, V, S>,
+    c_iter: Option<hashbrown::hash_map::IntoIter<K, V>>,
+}
+
+impl<'a, K, V, S> Iterator for Drain<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Clone,
+{
+    type Item = (K, V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(pair) = self.c_iter.as_mut().and_then(Iterator::next) {
+                return Some(pair);
+            }
+
+            if self.c_map_index == self.map.submaps.len() {
+                return None;
+            }
+
+            let taken = {
+                let mut submap = self.map.submaps[self.c_map_index].write();
+                let hasher = submap.hasher().clone();
+                self.map.len.fetch_sub(submap.len(), Ordering::Relaxed);
+                std::mem::replace(&mut *submap, HashMap::with_hasher(hasher))
+            };
+
+            self.c_iter = Some(taken.into_iter());
+            self.c_map_index += 1;
+        }
+    }
+}
+
+impl<'a, K, V, S> Drop for Drain<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    fn drop(&mut self) {
+        for locked in self.map.submaps[self.c_map_index..].iter() {
+            let mut submap = locked.write();
+            self.map.len.fetch_sub(submap.len(), Ordering::Relaxed);
+            submap.clear();
+        }
+    }
+}
+
+/// An iterator moving the pairs out of a DashMap, one chunk at a time.
+pub struct IntoIter<K, V, S = SeaBuildHasher> {
+    submaps: std::vec::IntoIter<RwLock<HashMap<K, V, S>>>,
+    c_iter: Option<hashbrown::hash_map::IntoIter<K, V>>,
+}
+
+impl<K, V, S> Iterator for IntoIter<K, V, S> {
+    type Item = (K, V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(pair) = self.c_iter.as_mut().and_then(Iterator::next) {
+                return Some(pair);
+            }
+
+            self.c_iter = Some(self.submaps.next()?.into_inner().into_iter());
+        }
+    }
+}
+
+impl<K, V, S> IntoIterator for DashMap<K, V, S>
+where
+    K: Hash + Eq,
+{
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, S>;
+
+    /// Consume the map. No locking is needed since the map is owned.
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            submaps: self.submaps.into_vec().into_iter(),
+            c_iter: None,
+        }
+    }
+}
+
+/// A read only iterator interface to a chunk.
+pub struct Chunk<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    inner: RwLockReadGuard<'a, HashMap<K, V, S>>,
+}
+
+impl<'a, K: 'a, V: 'a, S: 'a> Chunk<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    #[inline]
+    fn new(inner: RwLockReadGuard<'a, HashMap<K, V, S>>) -> Self {
+        Self { inner }
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.inner.iter()
+    }
+
+    /// Get the amount of elements stored within the chunk.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Check if the chunk is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Get the amount of elements the chunk can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+}
+
+/// A read-write iterator interface to a chunk.
+pub struct ChunkMut<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    inner: RwLockWriteGuard<'a, HashMap<K, V, S>>,
+    len: &'a AtomicUsize,
+}
+
+impl<'a, K: 'a, V: 'a, S: 'a> ChunkMut<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    #[inline]
+    fn new(inner: RwLockWriteGuard<'a, HashMap<K, V, S>>, len: &'a AtomicUsize) -> Self {
+        Self { inner, len }
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.inner.iter()
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.inner.iter_mut()
+    }
+
+    /// Get the amount of elements stored within the chunk.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Check if the chunk is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Get the amount of elements the chunk can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Remove all elements from the chunk while keeping its allocated memory.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.len.fetch_sub(self.inner.len(), Ordering::Relaxed);
+        self.inner.clear();
+    }
+}
+
+/// A write locked chunk of a DashMap which updates the length of the map when dropped.
+pub struct DashMapRawMut<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    inner: RwLockWriteGuard<'a, HashMap<K, V, S>>,
+    len: &'a AtomicUsize,
+    len_before: usize,
+}
+
+impl<'a, K, V, S> Deref for DashMapRawMut<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    type Target = HashMap<K, V, S>;
+
+    #[inline]
+    fn deref(&self) -> &HashMap<K, V, S> {
+        &self.inner
+    }
+}
+
+impl<'a, K, V, S> DerefMut for DashMapRawMut<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut HashMap<K, V, S> {
+        &mut self.inner
+    }
+}
+
+impl<'a, K, V, S> Drop for DashMapRawMut<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    #[inline]
+    fn drop(&mut self) {
+        let len_after = self.inner.len();
+
+        if len_after > self.len_before {
+            self.len
+                .fetch_add(len_after - self.len_before, Ordering::Relaxed);
+        } else {
+            self.len
+                .fetch_sub(self.len_before - len_after, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A guard holding the read lock of every chunk in a DashMap.
+pub struct AllShardsGuard<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    chunks: Vec<Chunk<'a, K, V, S>>,
+}
+
+impl<'a, K: 'a, V: 'a, S: 'a> AllShardsGuard<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    /// Iterate over the (K, V) pairs stored in the map.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.chunks.iter().flat_map(|chunk| chunk.iter())
+    }
+
+    /// Get the amount of elements stored within the map.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.inner.len()).sum()
+    }
+
+    /// Check if the map is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A shared reference into a DashMap.
+pub struct DashMapRef<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    ptr_k: &'a K,
+    ptr: OwningRef<RwLockReadGuard<'a, HashMap<K, V, S>>, V>,
+}
+
+impl<'a, K, V, S> DashMapRef<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    #[inline]
+    fn new<Q>(submap: RwLockReadGuard<'a, HashMap<K, V, S>>, key: &Q) -> Option<Self>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut ptr_k: *const K = std::ptr::null();
+
+        OwningRef::new(submap)
+            .try_map(|v| match v.get_key_value(key) {
+                Some((k, v)) => {
+                    ptr_k = k;
+                    Ok(v)
+                }
+                None => Err(()),
+            })
+            .ok()
+            .map(|ptr| DashMapRef {
+                ptr_k: unsafe { &*ptr_k },
+                ptr,
+            })
+    }
+
+    /// Get the key of the entry.
+    #[inline]
+    pub fn key(&self) -> &K {
+        self.ptr_k
+    }
+
+    /// Get the key and the value of the entry.
+    #[inline]
+    pub fn pair(&self) -> (&K, &V) {
+        (self.ptr_k, &self.ptr)
+    }
+}
+
+impl<'a, K, V, S> Deref for DashMapRef<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    type Target = V;
+
+    #[inline]
+    fn deref(&self) -> &V {
+        &*self.ptr
+    }
+}
+
+/// A unique reference into a DashMap.
+pub struct DashMapRefMut<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    ptr_k: &'a K,
+    ptr: OwningRefMut<RwLockWriteGuard<'a, HashMap<K, V, S>>, V>,
+}
+
+impl<'a, K, V, S> DashMapRefMut<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    #[inline]
+    fn new<Q>(submap: RwLockWriteGuard<'a, HashMap<K, V, S>>, key: &Q) -> Option<Self>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut ptr_k: *const K = std::ptr::null();
+
+        OwningRefMut::new(submap)
+            .try_map_mut(|v| match v.raw_entry_mut().from_key(key) {
+                RawEntryMut::Occupied(entry) => {
+                    let (k, v) = entry.into_key_value();
+                    ptr_k = k;
+                    Ok(v)
+                }
+                RawEntryMut::Vacant(_) => Err(()),
+            })
+            .ok()
+            .map(|ptr| DashMapRefMut {
+                ptr_k: unsafe { &*ptr_k },
+                ptr,
+            })
+    }
+
+    /// Get the key of the entry.
+    #[inline]
+    pub fn key(&self) -> &K {
+        self.ptr_k
+    }
+
+    /// Get the key and the value of the entry.
+    #[inline]
+    pub fn pair(&self) -> (&K, &V) {
+        (self.ptr_k, &self.ptr)
+    }
+
+    /// Get the key and a unique reference to the value of the entry.
+    #[inline]
+    pub fn pair_mut(&mut self) -> (&K, &mut V) {
+        (self.ptr_k, &mut self.ptr)
+    }
+}
+
+impl<'a, K, V, S> Deref for DashMapRefMut<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    type Target = V;
+
+    #[inline]
+    fn deref(&self) -> &V {
+        &*self.ptr
+    }
+}
+
+impl<'a, K, V, S> DerefMut for DashMapRefMut<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut V {
+        &mut *self.ptr
+    }
+}
+
+/// An upgradable reference into a DashMap.
+pub struct DashMapUpgradableRef<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    ptr_k: &'a K,
+    ptr: OwningRef<RwLockUpgradableReadGuard<'a, HashMap<K, V, S>>, V>,
+}
+
+impl<'a, K, V, S> DashMapUpgradableRef<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Upgrade to a unique reference without releasing the lock in between.
+    #[inline]
+    pub fn upgrade(self) -> DashMapRefMut<'a, K, V, S> {
+        let key = self.ptr_k;
+        let guard = RwLockUpgradableReadGuard::upgrade(self.ptr.into_owner());
+
+        // No writer could have touched the submap while the upgradable lock was held
+        // so the entry is guaranteed to still be present.
+        DashMapRefMut::new(guard, key).unwrap()
+    }
+
+    /// Get the key of the entry.
+    #[inline]
+    pub fn key(&self) -> &K {
+        self.ptr_k
+    }
+}
+
+impl<'a, K, V, S> Deref for DashMapUpgradableRef<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    type Target = V;
+
+    #[inline]
+    fn deref(&self) -> &V {
+        &self.ptr
+    }
+}
+
+/// A unique reference into a DashMap.
+pub enum DashMapRefAny<'a, K, V, S = SeaBuildHasher>
+where
+    K: Hash + Eq,
+{
+    Shared(DashMapRef<'a, K, V, S>),
+    Unique(DashMapRefMut<'a, K, V, S>),
+    Marker(PhantomData<&'a K>, PhantomData<&'a V>),
+}
+
+impl<'a, K, V, S> Deref for DashMapRefAny<'a, K, V, S>
+where
+    K: Hash + Eq,
+{
+    type Target = V;
+
+    #[inline]
+    fn deref(&self) -> &V {
+        match self {
+            DashMapRefAny::Shared(r) => &*r,
+            DashMapRefAny::Unique(r) => &*r,
+            DashMapRefAny::Marker(_, _) => unreachable!(),
+        }
+    }
+}
+
+/// Hashes of the keys currently computed by `get_or_compute`.
+#[derive(Default)]
+struct InFlight {
+    keys: Mutex<HashSet<u64>>,
+    done: Condvar,
+}
+
+/// Marks a key as no longer computed when dropped and wakes the waiting callers.
+struct Flight<'a> {
+    in_flight: &'a InFlight,
+    hash: u64,
+}
+
+impl<'a> Drop for Flight<'a> {
+    fn drop(&mut self) {
+        self.in_flight.keys.lock().remove(&self.hash);
+        self.in_flight.done.notify_all();
+    }
+}
+
+/// Uncounts an entry taken out by `take_replace` if the replacement function unwinds.
+struct Removed<'a> {
+    len: &'a AtomicUsize,
+}
+
+impl<'a> Drop for Removed<'a> {
+    fn drop(&mut self) {
+        self.len.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Whether `get_or_insert_entry` inserted the element or found an existing one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Insertion {
+    /// The key did not exist and the default was inserted.
+    Created,
+
+    /// The key already existed.
+    Existed,
+}
+
+/// A error possibly returned by the try_get family of methods for DashMap.
+pub enum TryGetError {
+    /// Returned if the key did not exist in the map.
+    InvalidKey,
+
+    /// Returned if the operation was going to block.
+    WouldBlock,
+
+    /// Returned if the lock did not become available within the specified timeout.
+    DidNotResolve,
+}
+
+/// Alias for a Result with TryGetError as it's error type.
+pub type TryGetResult<T> = Result<T, TryGetError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::assert_send_sync;
+
+    fn use_map(mut e: DashMapRefMut<i32, i32>) {
+        *e *= 2;
+    }
+
+    #[test]
+    fn move_deref() {
+        let map = DashMap::default();
+        map.insert(3, 69);
+        let e = map.index_mut(&3);
+        use_map(e);
+        println!("e: {}", *map.index_mut(&3));
+    }
+
+    #[test]
+    fn insert_then_assert_1024() {
+        let map = DashMap::default();
+
+        for i in 0..1024_i32 {
+            map.insert(i, i * 2);
+        }
+
+        map.alter_all(|v| v * 2);
+
+        for i in 0..1024_i32 {
+            assert_eq!(i * 4, *map.get(&i).unwrap());
+        }
+    }
+
+    #[test]
+    fn reset_values_keeps_keys() {
+        let map = DashMap::default();
+
+        for i in 0..1024_i32 {
+            map.insert(i, i * 7);
+        }
+
+        map.reset_values(0);
+
+        assert_eq!(map.len(), 1024);
+        for i in 0..1024_i32 {
+            assert_eq!(0, *map.get(&i).unwrap());
+        }
+    }
+
+    #[test]
+    fn insert_then_iter_1024() {
+        let map = DashMap::default();
+
+        for i in 0..1024_i32 {
+            map.insert(i, i * 2);
+        }
+
+        map.alter_all(|v| v * 2);
+
+        assert_eq!(map.iter().count(), 1024);
+    }
+
+    #[test]
+    fn for_each_matches_iter() {
+        let map = DashMap::default();
+
+        for i in 0..1024_i32 {
+            map.insert(i, i * 3);
+        }
+
+        let mut via_iter: Vec<(i32, i32)> = map.iter().map(|r| (*r.key(), *r.value())).collect();
+        let mut via_for_each = Vec::new();
+        map.for_each(|k, v| via_for_each.push((*k, *v)));
+
+        via_iter.sort();
+        via_for_each.sort();
+        assert_eq!(via_for_each.len(), 1024);
+        assert_eq!(via_iter, via_for_each);
+    }
+
+    #[test]
+    fn iter_progresses_past_write_locked_chunk() {
+        let map = DashMap::new(4);
+
+        for i in 0..1024_i32 {
+            map.insert(i, i);
+        }
+
+        let last = map.chunks_count() - 1;
+        let held_key = (0..1024).find(|i| map.determine_map(i) == last).unwrap();
+        let before_held = (0..1024).filter(|i| map.determine_map(i) != last).count();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        std::thread::scope(|s| {
+            let map = &map;
+            let guard = map.get_raw_mut_from_key(&held_key);
+
+            s.spawn(move || {
+                for r in map.iter() {
+                    tx.send(*r.key()).unwrap();
+                }
+            });
+
+            for _ in 0..before_held {
+                rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            }
+
+            assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+            drop(guard);
+        });
+
+        assert_eq!(rx.iter().count(), 1024 - before_held);
+    }
+
+    #[test]
+    fn lock_all_read_blocks_writer() {
+        let map = DashMap::new(4);
+
+        for i in 0..256_i32 {
+            map.insert(i, i * 2);
+        }
+
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        std::thread::scope(|s| {
+            let map = &map;
+            let guard = map.lock_all_read();
+
+            s.spawn(move || {
+                map.insert(256, 512);
+                tx.send(()).unwrap();
+            });
+
+            assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+            assert_eq!(guard.len(), 256);
+
+            let mut snapshot: Vec<(i32, i32)> = guard.iter().map(|(k, v)| (*k, *v)).collect();
+            snapshot.sort();
+            assert_eq!(snapshot, (0..256).map(|i| (i, i * 2)).collect::<Vec<_>>());
+
+            drop(guard);
+            rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        });
+
+        assert_eq!(map.len(), 257);
+    }
+
+    #[test]
+    fn try_get_with_timeout_gives_up_on_held_chunk() {
+        let map = DashMap::new(2);
+        map.insert(1_i32, 2_i32);
+
+        let barrier = std::sync::Barrier::new(2);
+
+        std::thread::scope(|s| {
+            let map = &map;
+            let barrier = &barrier;
+
+            s.spawn(move || {
+                let guard = map.get_mut(&1).unwrap();
+                barrier.wait();
+                barrier.wait();
+                drop(guard);
+            });
+
+            barrier.wait();
+
+            let timeout = Duration::from_millis(20);
+            assert!(matches!(
+                map.try_get_with_timeout(&1, timeout),
+                Err(TryGetError::DidNotResolve)
+            ));
+            assert!(matches!(
+                map.try_get_mut_with_timeout(&1, timeout),
+                Err(TryGetError::DidNotResolve)
+            ));
+
+            barrier.wait();
+        });
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(*map.try_get_with_timeout(&1, timeout).ok().unwrap(), 2);
+        assert!(matches!(
+            map.try_get_mut_with_timeout(&2, timeout),
+            Err(TryGetError::InvalidKey)
+        ));
+    }
+
+    #[test]
+    fn async_get_waits_for_held_chunk() {
+        use futures::executor::block_on;
+
+        let map = DashMap::new(2);
+        map.insert(1_i32, 2_i32);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        std::thread::scope(|s| {
+            let map = &map;
+            let guard = map.get_mut(&1).unwrap();
+
+            s.spawn(move || {
+                let r = block_on(map.async_get(1)).unwrap();
+                tx.send(*r).unwrap();
+                drop(r);
+
+                *block_on(map.async_get_mut(1)).unwrap() += 1;
+                tx.send(0).unwrap();
+            });
+
+            assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+            drop(guard);
+
+            assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), 2);
+            rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        });
+
+        assert_eq!(*map.get(&1).unwrap(), 3);
+    }
+
+    #[test]
+    fn into_iter_moves_all_pairs() {
+        let map = DashMap::default();
+
+        for i in 0..1024_i32 {
+            map.insert(i, i.to_string());
+        }
+
+        let mut pairs: Vec<(i32, String)> = map.into_iter().collect();
+        pairs.sort();
+
+        assert_eq!(pairs.len(), 1024);
+        assert_eq!(
+            pairs,
+            (0..1024).map(|i| (i, i.to_string())).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn drain_empties_map() {
+        let map = DashMap::default();
+
+        for i in 0..1024_i32 {
+            map.insert(i, i * 2);
+        }
+
+        let mut pairs: Vec<(i32, i32)> = map.drain().collect();
+        pairs.sort();
+
+        assert_eq!(pairs, (0..1024).map(|i| (i, i * 2)).collect::<Vec<_>>());
+        assert!(map.is_empty());
+
+        map.insert(1, 2);
+        map.insert(2, 4);
+        assert_eq!(map.drain().take(1).count(), 1);
+        assert!(map.is_empty());
+        assert!(map.get(&1).is_none() && map.get(&2).is_none());
+    }
+
+    #[test]
+    fn keys_and_values_match_len() {
+        let map = DashMap::default();
+
+        for i in 0..512_i32 {
+            map.insert(i, i * 2);
+        }
+
+        assert_eq!(map.keys().count(), map.len());
+        assert_eq!(map.values().count(), map.len());
+
+        for mut v in map.values_mut() {
+            *v += 1;
+        }
+
+        let mut keys: Vec<i32> = map.keys().map(|k| *k).collect();
+        keys.sort();
+        assert_eq!(keys, (0..512).collect::<Vec<_>>());
+        assert!(map.values().all(|v| *v % 2 == 1));
+    }
+
+    #[test]
+    fn get_many_spread_and_clustered() {
+        let map = DashMap::new(3);
+
+        for i in 0..256_i32 {
+            map.insert(i, i * 2);
+        }
+
+        let clustered: Vec<i32> = (0..256).filter(|i| map.determine_map(i) == 0).collect();
+        let spread: Vec<i32> = (0..map.chunks_count())
+            .map(|chunk| (0..256).find(|i| map.determine_map(i) == chunk).unwrap())
+            .collect();
+        assert!(clustered.len() > 1);
+
+        let keys: Vec<i32> = clustered
+            .iter()
+            .chain(spread.iter())
+            .cloned()
+            .chain(vec![300, clustered[0]])
+            .collect();
+        let key_refs: Vec<&i32> = keys.iter().collect();
+
+        let found = map.get_many(&key_refs);
+        assert_eq!(found.len(), keys.len());
+
+        for (key, r) in keys.iter().zip(found.iter()) {
+            match r {
+                Some(r) => {
+                    assert_eq!(r.key(), key);
+                    assert_eq!(*r.value(), key * 2);
+                }
+                None => assert_eq!(*key, 300),
+            }
+        }
+
+        drop(found);
+        map.insert(0, 0);
+    }
+
+    #[test]
+    fn swap_same_and_cross_chunk() {
+        let map = DashMap::new(2);
+
+        for i in 0..64_i32 {
+            map.insert(i, i.to_string());
+        }
+
+        let same: Vec<i32> = (0..64)
+            .filter(|i| map.determine_map(i) == 0)
+            .take(2)
+            .collect();
+        let cross = (0..64).find(|i| map.determine_map(i) != 0).unwrap();
+
+        assert!(map.swap(&same[0], &same[1]));
+        assert_eq!(*map.get(&same[0]).unwrap(), same[1].to_string());
+        assert_eq!(*map.get(&same[1]).unwrap(), same[0].to_string());
+
+        assert!(map.swap(&cross, &same[0]));
+        assert_eq!(*map.get(&cross).unwrap(), same[1].to_string());
+        assert_eq!(*map.get(&same[0]).unwrap(), cross.to_string());
+
+        assert!(map.swap(&cross, &cross));
+        assert!(!map.swap(&100, &100));
+        assert!(!map.swap(&same[1], &100));
+        assert_eq!(*map.get(&same[1]).unwrap(), same[0].to_string());
+    }
+
+    #[test]
+    fn alter_key_same_and_cross_chunk() {
+        let map = DashMap::new(2);
+
+        for i in 0..64_i32 {
+            map.insert(i, i.to_string());
+        }
+
+        let same: Vec<i32> = (64..1024)
+            .filter(|i| map.determine_map(i) == map.determine_map(&0))
+            .take(2)
+            .collect();
+        let cross = (64..1024)
+            .find(|i| map.determine_map(i) != map.determine_map(&1) && !same.contains(i))
+            .unwrap();
+
+        assert!(map.alter_key(&0, same[0]));
+        assert!(!map.contains_key(&0));
+        assert_eq!(*map.get(&same[0]).unwrap(), "0");
+
+        assert!(map.alter_key(&1, cross));
+        assert!(!map.contains_key(&1));
+        assert_eq!(*map.get(&cross).unwrap(), "1");
+        assert_eq!(map.len(), 64);
+
+        assert!(map.alter_key(&2, cross));
+        assert_eq!(*map.get(&cross).unwrap(), "2");
+        assert_eq!(map.len(), 63);
+
+        assert!(!map.alter_key(&0, same[1]));
+        assert!(!map.contains_key(&same[1]));
+    }
+
+    #[test]
+    fn swap_concurrent_opposite_orders() {
+        use rayon::prelude::*;
+
+        let map = DashMap::new(2);
+        map.insert(1_i32, 1_i32);
+        map.insert(2, 2);
+
+        (0..10_000).into_par_iter().for_each(|i| {
+            if i % 2 == 0 {
+                assert!(map.swap(&1, &2));
+            } else {
+                assert!(map.swap(&2, &1));
+            }
+        });
+
+        assert_eq!(*map.get(&1).unwrap(), 1);
+        assert_eq!(*map.get(&2).unwrap(), 2);
+    }
+
+    #[test]
+    fn take_replace_rebuilds_string() {
+        let map = DashMap::default();
+        map.insert(1_i32, "foo".to_string());
+
+        assert!(map.take_replace(&1, |v| {
+            let mut bytes = v.into_bytes();
+            bytes.reverse();
+            String::from_utf8(bytes).unwrap() + "bar"
+        }));
+        assert!(!map.take_replace(&2, |v| v));
+
+        assert_eq!(*map.get(&1).unwrap(), "oofbar");
+        assert!(!map.contains_key(&2));
+    }
+
+    #[test]
+    fn take_replace_panic_keeps_len() {
+        let map = DashMap::default();
+        map.insert(1_i32, "foo".to_string());
+        map.insert(2_i32, "bar".to_string());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            map.take_replace(&1, |_| panic!("replacement failed"))
+        }));
+
+        assert!(result.is_err());
+        assert!(!map.contains_key(&1));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_from_iter_1m() {
+        use rayon::prelude::*;
+
+        let map = DashMap::par_from_iter((0..1_000_000_i32).into_par_iter().map(|i| (i, i)));
+
+        assert_eq!(map.len(), 1_000_000);
+        assert!((0..1_000_000_i32).all(|i| *map.get(&i).unwrap() == i));
+    }
+
+    #[test]
+    fn lookups_agree_on_present_and_missing() {
+        use futures::executor::block_on;
+
+        let map = DashMap::default();
+
+        for i in (0..512_i32).filter(|i| i % 2 == 0) {
+            map.insert(i, i + 1);
+        }
+
+        for i in 0..512_i32 {
+            let expected = if i % 2 == 0 { Some(i + 1) } else { None };
+
+            assert_eq!(map.get(&i).map(|v| *v), expected);
+            assert_eq!(map.get_mut(&i).map(|v| *v), expected);
+            assert_eq!(map.try_get(&i).ok().map(|v| *v), expected);
+            assert_eq!(map.try_get_mut(&i).ok().map(|v| *v), expected);
+            assert_eq!(block_on(map.async_get(i)).map(|v| *v), expected);
+            assert_eq!(block_on(map.async_get_mut(i)).map(|v| *v), expected);
+            assert_eq!(map.contains_key(&i), expected.is_some());
+            assert_eq!(*map.get_or_insert(&i, 0), expected.unwrap_or(0));
+        }
+    }
+
+    #[test]
+    fn insert_and_get_mut_then_mutate() {
+        let map = DashMap::default();
+
+        {
+            let mut v = map.insert_and_get_mut("foo".to_string(), vec![1_i32]);
+            assert_eq!(v.key(), "foo");
+            v.push(2);
+        }
+
+        assert_eq!(*map.get("foo").unwrap(), vec![1, 2]);
+        map.insert_and_get_mut("foo".to_string(), vec![3]).push(4);
+
+        assert_eq!(*map.get("foo").unwrap(), vec![3, 4]);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn eq_compares_contents() {
+        let a = DashMap::new(2);
+        let b = DashMap::new(5);
+
+        for i in 0..256_i32 {
+            a.insert(i, i * 2);
+            b.insert(i, i * 2);
+        }
+
+        assert!(a == b);
+        assert!(a == a);
+
+        b.insert(7, 0);
+        assert!(a != b);
+
+        b.insert(7, 14);
+        b.insert(256, 512);
+        assert!(a != b);
+        assert!(b != a);
+    }
+
+    #[test]
+    fn eq_both_directions_rayon() {
+        use rayon::prelude::*;
+
+        let a = DashMap::new(2);
+        let b = DashMap::new(2);
+
+        for i in 0..1024_i32 {
+            a.insert(i, i);
+            b.insert(i, i);
+        }
+
+        (0..256_i32).into_par_iter().for_each(|i| match i % 3 {
+            0 => assert!(a == b),
+            1 => assert!(b == a),
+            _ => {
+                a.insert(i, i);
+                b.insert(i, i);
+            }
+        });
+    }
+
+    #[test]
+    fn insert_full_reports_new() {
+        let map = DashMap::default();
+
+        {
+            let (new, mut v) = map.insert_full(1_i32, 10_i32);
+            assert!(new);
+            *v += 1;
+        }
+
+        assert_eq!(*map.get(&1).unwrap(), 11);
+
+        {
+            let (new, mut v) = map.insert_full(1, 20);
+            assert!(!new);
+            assert_eq!(*v, 20);
+            *v *= 2;
+        }
+
+        assert_eq!(*map.get(&1).unwrap(), 40);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn get_or_compute_single_flight() {
+        use rayon::prelude::*;
+
+        let map = DashMap::default();
+        let computed = AtomicUsize::new(0);
+
+        (0..64).into_par_iter().for_each(|_| {
+            let v = map.get_or_compute(&"foo", || {
+                computed.fetch_add(1, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(50));
+                42_i32
+            });
+
+            assert_eq!(*v, 42);
+        });
+
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn get_or_compute_moves_into_value() {
+        let map = DashMap::default();
+        let value = "foo".to_string();
+
+        assert_eq!(*map.get_or_compute(&1_i32, move || value), "foo");
+        assert_eq!(*map.get_or_compute(&1, || unreachable!()), "foo");
+    }
+
+    #[derive(Default)]
+    struct FnvHasher(u64);
+
+    impl Hasher for FnvHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x100_0000_01b3);
+            }
+        }
+    }
+
+    #[test]
+    fn custom_hasher() {
+        let map = DashMap::with_hasher(4, BuildHasherDefault::<FnvHasher>::default());
+
+        for i in 0..1000_i32 {
+            map.insert(i, i * 2);
+        }
+
+        assert_eq!(map.len(), 1000);
+        assert!((0..1000).all(|i| *map.get(&i).unwrap() == i * 2));
+        assert!(
+            map.chunks()
+                .filter(|chunk| chunk.iter().next().is_some())
+                .count()
+                > 1
+        );
+
+        assert_eq!(map.remove(&10), Some((10, 20)));
+        assert!(!map.contains_key(&10));
+    }
+
+    #[test]
+    fn chunk_lens_sum_to_len() {
+        let map = DashMap::default();
+
+        for i in 0..1024_i32 {
+            map.insert(i, i);
+        }
+
+        assert_eq!(
+            map.chunks().map(|chunk| chunk.len()).sum::<usize>(),
+            map.len()
+        );
+        assert!(map.chunks().all(|chunk| chunk.capacity() >= chunk.len()));
+
+        if let Some(mut chunk) = map.chunks_write().find(|chunk| !chunk.is_empty()) {
+            let removed = chunk.len();
+            chunk.clear();
+            assert!(chunk.is_empty());
+            drop(chunk);
+
+            assert_eq!(map.len(), 1024 - removed);
+        }
+
+        assert_eq!(
+            map.chunks_write().map(|chunk| chunk.len()).sum::<usize>(),
+            map.len()
+        );
+    }
+
+    #[test]
+    fn refs_expose_key() {
+        let map = DashMap::default();
+        map.insert("foo".to_string(), 1_i32);
+
+        let computed = ["f", "o", "o"].concat();
+
+        {
+            let mut r = map.get_mut(computed.as_str()).unwrap();
+            assert_eq!(r.key(), "foo");
+
+            let (k, v) = r.pair_mut();
+            assert_eq!(k, "foo");
+            *v += 1;
+        }
+
+        let r = map.get("foo").unwrap();
+        assert_eq!(r.pair(), (&"foo".to_string(), &2));
+    }
+
+    #[test]
+    fn with_capacity_smaller_than_chunks() {
+        let map = DashMap::with_capacity(8, 4);
+        assert_eq!(map.chunks_count(), 256);
+        assert!(map.submaps.iter().all(|s| s.read().capacity() >= 1));
+
+        for i in 0..4_i32 {
+            map.insert(i, i);
+        }
+
+        assert_eq!(map.len(), 4);
+        assert!((0..4_i32).all(|i| *map.get(&i).unwrap() == i));
+    }
+
+    #[test]
+    fn increment_rayon() {
+        use rayon::prelude::*;
+
+        let map: DashMap<&str, u64> = DashMap::default();
+
+        (0..10_000).into_par_iter().for_each(|_| {
+            map.increment(&"hits");
+        });
+
+        assert_eq!(*map.get("hits").unwrap(), 10_000);
+        assert_eq!(map.increment(&"hits"), 10_001);
+        assert_eq!(map.increment(&"misses"), 1);
+    }
+
+    #[test]
+    fn add_assign_rayon() {
+        use rayon::prelude::*;
+
+        let map: DashMap<&str, u64> = DashMap::default();
+
+        (0..10_000_u64).into_par_iter().for_each(|i| {
+            map.add_assign("total", i);
+        });
+
+        assert_eq!(*map.get("total").unwrap(), (0..10_000).sum::<u64>());
+        map.add_assign("other", 5);
+        assert_eq!(*map.get("other").unwrap(), 5);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn map_values_to_len() {
+        let map = DashMap::new(3);
+
+        for i in 0..256_usize {
+            map.insert(i, "x".repeat(i));
+        }
+
+        let lens = map.map_values(|v| v.len());
+
+        assert_eq!(lens.len(), 256);
+        assert!((0..256).all(|i| *lens.get(&i).unwrap() == i));
+        assert!((0..256).all(|i| lens.determine_map(&i) == map.determine_map(&i)));
+    }
+
+    #[test]
+    fn iter_owned_releases_chunk_locks() {
+        let map = DashMap::new(4);
+
+        for i in 0..1024_i32 {
+            map.insert(i, i * 2);
+        }
+
+        let mut seen = Vec::new();
+
+        for (k, v) in map.iter_owned() {
+            assert!(map.try_get_mut(&k).is_ok());
+            map.insert(k, v + 1);
+            seen.push((k, v));
+        }
+
+        seen.sort();
+        assert_eq!(seen, (0..1024).map(|i| (i, i * 2)).collect::<Vec<_>>());
+        assert!((0..1024_i32).all(|i| *map.get(&i).unwrap() == i * 2 + 1));
+    }
+
+    #[test]
+    fn insert_returns_previous() {
+        let map = DashMap::default();
+        assert_eq!(map.insert("foo", 1_i32), None);
+        assert_eq!(map.insert("foo", 2), Some(1));
+        assert_eq!(*map.get("foo").unwrap(), 2);
+    }
 
-        let guard: *mut RwLockWriteGuard<'_, HashMap<K, V>> =
-            Arc::into_raw(Arc::new(self.map.submaps[self.c_map_index].write())) as _;
-        let gr: &mut RwLockWriteGuard<'_, HashMap<K, V>> = unsafe { &mut *guard };
-        let iter = gr.iter_mut();
+    #[test]
+    fn clone_keeps_all_keys() {
+        let map = DashMap::default();
 
-        std::mem::replace(
-            &mut self.c_iter,
-            Some((unsafe { Arc::from_raw(guard) }, iter)),
-        );
+        for i in 0..1024_i32 {
+            map.insert(i, i.to_string());
+        }
 
-        self.c_map_index += 1;
-        self.next()
+        let cloned = map.clone();
+        map.clear();
+
+        assert_eq!(cloned.len(), 1024);
+        assert!((0..1024_i32).all(|i| *cloned.get(&i).unwrap() == i.to_string()));
     }
-}
 
-impl<'a, K, V> Iterator for IterMut<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    type Item = DashMapIterRefMut<'a, K, V>;
+    #[test]
+    fn get_or_insert_entry_created_then_existed() {
+        let map = DashMap::default();
 
-    #[inline]
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Some(c_iter) = &mut self.c_iter {
-            if let Some(i) = c_iter.1.next() {
-                let guard = Some(c_iter.0.clone());
-                let ptr_k = unsafe { &*(i.0 as *const _) };
-                let ptr_v = unsafe { &mut *(i.1 as *mut _) };
+        {
+            let (insertion, mut v) = map.get_or_insert_entry(&"foo", 1_i32);
+            assert_eq!(insertion, Insertion::Created);
+            *v += 1;
+        }
 
-                return Some(DashMapIterRefMut {
-                    guard,
-                    ptr_k,
-                    ptr_v,
-                });
-            }
+        let (insertion, v) = map.get_or_insert_entry(&"foo", 10);
+        assert_eq!(insertion, Insertion::Existed);
+        assert_eq!(*v, 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let map = DashMap::default();
+
+        for i in 0..1024_i32 {
+            map.insert(i.to_string(), i);
         }
 
-        self.slow_path_new_chunk()
+        let json = serde_json::to_string(&map).unwrap();
+        let reloaded: DashMap<String, i32> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(reloaded.len(), 1024);
+        map.for_each(|k, v| assert_eq!(*reloaded.get(k).unwrap(), *v));
     }
-}
 
-/// A read only iterator interface to a chunk.
-pub struct Chunk<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    inner: RwLockReadGuard<'a, HashMap<K, V>>,
-}
+    #[test]
+    fn snapshot_and_clear_takes_all() {
+        let map = DashMap::default();
 
-impl<'a, K: 'a, V: 'a> Chunk<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    #[inline]
-    fn new(inner: RwLockReadGuard<'a, HashMap<K, V>>) -> Self {
-        Self { inner }
+        for i in 0..1024_i32 {
+            map.insert(i, i * 2);
+        }
+
+        let mut entries = map.snapshot_and_clear();
+        entries.sort();
+
+        assert_eq!(entries, (0..1024).map(|i| (i, i * 2)).collect::<Vec<_>>());
+        assert!(map.is_empty());
     }
 
-    #[inline]
-    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
-        self.inner.iter()
+    #[test]
+    fn len_counter_tracks_concurrent_changes() {
+        let map = DashMap::default();
+
+        let expected: usize = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..8_i32)
+                .map(|t| {
+                    let map = &map;
+
+                    s.spawn(move || {
+                        let mut count = 0;
+
+                        for i in t * 1000..(t + 1) * 1000 {
+                            if map.insert(i, i).is_none() {
+                                count += 1;
+                            }
+
+                            if map.insert(i, i + 1).is_none() {
+                                count += 1;
+                            }
+
+                            if i % 3 == 0 && map.remove(&i).is_some() {
+                                count -= 1;
+                            }
+                        }
+
+                        count
+                    })
+                })
+                .collect();
+
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        });
+
+        assert_eq!(map.len(), expected);
+        assert_eq!(map.len(), map.iter().count());
+
+        map.retain(|k, _| k % 2 == 0);
+        map.get_raw_mut_from_key(&-1).insert(-1, -1);
+        assert_eq!(map.len(), map.iter().count());
+
+        map.clear();
+        assert_eq!(map.len(), 0);
     }
-}
 
-/// A read-write iterator interface to a chunk.
-pub struct ChunkMut<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    inner: RwLockWriteGuard<'a, HashMap<K, V>>,
-}
+    #[test]
+    fn get_arc_outlives_map() {
+        let map = DashMap::default();
+        map.insert("foo", Arc::new("bar".to_string()));
 
-impl<'a, K: 'a, V: 'a> ChunkMut<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    #[inline]
-    fn new(inner: RwLockWriteGuard<'a, HashMap<K, V>>) -> Self {
-        Self { inner }
+        let value = map.get_arc("foo").unwrap();
+        assert!(map.get_arc("baz").is_none());
+        assert_eq!(Arc::strong_count(&value), 2);
+
+        drop(map);
+        assert_eq!(Arc::strong_count(&value), 1);
+        assert_eq!(*value, "bar");
     }
 
-    #[inline]
-    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
-        self.inner.iter()
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_iter_sum_matches_iter() {
+        let map = DashMap::default();
+
+        for i in 0..100_000_u64 {
+            map.insert(i, i * 3);
+        }
+
+        let par_sum: u64 = map.par_iter().map(|r| *r.key() + *r).sum();
+        let sum: u64 = map.iter().map(|r| *r.key() + *r).sum();
+
+        assert_eq!(par_sum, sum);
+        assert_eq!(map.par_iter().count(), 100_000);
     }
 
-    #[inline]
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
-        self.inner.iter_mut()
+    #[test]
+    fn get_with_releases_lock() {
+        let map = DashMap::default();
+        map.insert(1_i32, 2_i32);
+
+        assert_eq!(
+            map.get_mut_with(&1, |v| {
+                *v *= 21;
+                *v
+            }),
+            Some(42)
+        );
+        assert!(map.try_get_mut(&1).is_ok());
+
+        assert_eq!(map.get_with(&1, |v| v + 1), Some(43));
+        assert!(map.try_get_mut(&1).is_ok());
+
+        assert_eq!(map.get_with(&2, |v| *v), None);
+        assert_eq!(map.get_mut_with(&2, |v| *v), None);
     }
-}
 
-/// A shared reference into a DashMap.
-pub struct DashMapRef<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    ptr: OwningRef<RwLockReadGuard<'a, HashMap<K, V>>, V>,
-}
+    #[test]
+    fn upsert_batch_sums_overlapping() {
+        let map = DashMap::default();
 
-impl<'a, K, V> Deref for DashMapRef<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    type Target = V;
+        map.upsert_batch((0..100_i32).map(|i| (i, 1_u32)), |a, b| *a += b);
+        map.upsert_batch((50..150_i32).map(|i| (i, 2)), |a, b| *a += b);
+        map.upsert_batch(vec![(0, 10), (0, 20)], |a, b| *a += b);
 
-    #[inline]
-    fn deref(&self) -> &V {
-        &*self.ptr
+        assert_eq!(map.len(), 150);
+        assert_eq!(*map.get(&0).unwrap(), 31);
+        assert!((1..50).all(|i| *map.get(&i).unwrap() == 1));
+        assert!((50..100).all(|i| *map.get(&i).unwrap() == 3));
+        assert!((100..150).all(|i| *map.get(&i).unwrap() == 2));
     }
-}
 
-/// A unique reference into a DashMap.
-pub struct DashMapRefMut<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    ptr: OwningRefMut<RwLockWriteGuard<'a, HashMap<K, V>>, V>,
-}
+    #[test]
+    fn insert_many_10k() {
+        let map = DashMap::default();
 
-impl<'a, K, V> Deref for DashMapRefMut<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    type Target = V;
+        map.insert_many((0..10_000_i32).map(|i| (i, i)));
+        map.insert_many(vec![(0, 1), (0, 2)]);
 
-    #[inline]
-    fn deref(&self) -> &V {
-        &*self.ptr
+        assert_eq!(map.len(), 10_000);
+        assert_eq!(*map.get(&0).unwrap(), 2);
+        assert!((1..10_000).all(|i| *map.get(&i).unwrap() == i));
     }
-}
 
-impl<'a, K, V> DerefMut for DashMapRefMut<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    #[inline]
-    fn deref_mut(&mut self) -> &mut V {
-        &mut *self.ptr
+    #[test]
+    fn insert_then_iter_mut_map_1024() {
+        let map = DashMap::default();
+
+        for i in 0..1024_i32 {
+            map.insert(i, 4);
+        }
+
+        map.iter_mut().for_each(|mut r| *r *= 2);
+
+        assert_eq!(map.iter().fold(0, |acc, e| acc + *e), 8192);
     }
-}
 
-/// A unique reference into a DashMap.
-pub enum DashMapRefAny<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    Shared(DashMapRef<'a, K, V>),
-    Unique(DashMapRefMut<'a, K, V>),
-    Marker(PhantomData<&'a K>, PhantomData<&'a V>),
-}
+    #[test]
+    fn get_upgradable_then_upgrade() {
+        let map = DashMap::default();
+        map.insert(1, 10);
 
-impl<'a, K, V> Deref for DashMapRefAny<'a, K, V>
-where
-    K: Hash + Eq,
-{
-    type Target = V;
+        rayon::scope(|s| {
+            let r = map.get_upgradable(&1).unwrap();
+            assert!(map.try_get_mut(&1).is_err());
 
-    #[inline]
-    fn deref(&self) -> &V {
-        match self {
-            DashMapRefAny::Shared(r) => &*r,
-            DashMapRefAny::Unique(r) => &*r,
-            DashMapRefAny::Marker(_, _) => unreachable!(),
-        }
+            s.spawn(|_| *map.index_mut(&1) *= 100);
+            std::thread::sleep(Duration::from_millis(50));
+
+            assert_eq!(*map.index(&1), 10);
+            if *r == 10 {
+                let mut r = r.upgrade();
+                *r += 1;
+            }
+        });
+
+        assert_eq!(*map.index(&1), 1100);
     }
-}
 
-/// A error possibly returned by the try_get family of methods for DashMap.
-pub enum TryGetError {
-    /// Returned if the key did not exist in the map.
-    InvalidKey,
+    #[test]
+    fn shard_write_then_get() {
+        let map = DashMap::new(4);
+        map.insert(1_i32, 1_i32);
 
-    /// Returned if the operation was going to block.
-    WouldBlock,
+        let idx = map.shard_for_key(&2);
+        assert_eq!(idx, map.determine_map(&2));
 
-    /// Returned if the lock did not become available within the specified timeout.
-    DidNotResolve,
-}
+        map.shard_write(idx).insert(2, 4);
 
-/// Alias for a Result with TryGetError as it's error type.
-pub type TryGetResult<T> = Result<T, TryGetError>;
+        assert_eq!(*map.get(&2).unwrap(), 4);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.shard_read(idx).get(&2), Some(&4));
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn with_shard_amount_rounds_up() {
+        let single = DashMap::with_shard_amount(1);
+        single.insert(1_i32, 1_i32);
+        assert_eq!(single.chunks_count(), 1);
+        assert_eq!(*single.get(&1).unwrap(), 1);
+        assert_eq!(
+            DashMap::<i32, i32>::with_shard_amount(128).chunks_count(),
+            128
+        );
+        assert_eq!(
+            DashMap::<i32, i32>::with_shard_amount(100).chunks_count(),
+            128
+        );
 
-    fn use_map(mut e: DashMapRefMut<i32, i32>) {
-        *e *= 2;
+        let map = DashMap::with_shard_amount(3);
+        map.insert(1_i32, 2_i32);
+        assert_eq!(map.chunks_count(), 4);
+        assert_eq!(*map.get(&1).unwrap(), 2);
     }
 
     #[test]
-    fn move_deref() {
-        let map = DashMap::default();
-        map.insert(3, 69);
-        let e = map.index_mut(&3);
-        use_map(e);
-        println!("e: {}", *map.index_mut(&3));
+    #[should_panic(expected = "shard amount must be non-zero")]
+    fn with_shard_amount_zero_panics() {
+        DashMap::<i32, i32>::with_shard_amount(0);
     }
 
     #[test]
-    fn insert_then_assert_1024() {
-        let map = DashMap::default();
+    fn shard_keys_match_determine_map() {
+        let map = DashMap::new(4);
 
-        for i in 0..1024_i32 {
-            map.insert(i, i * 2);
+        for i in 0..256_i32 {
+            map.insert(i, i);
         }
 
-        map.alter_all(|v| v * 2);
-
-        for i in 0..1024_i32 {
-            assert_eq!(i * 4, *map.get(&i).unwrap());
+        for idx in 0..map.chunks_count() {
+            let mut keys = map.shard_keys(idx);
+            keys.sort();
+            let expected: Vec<i32> = (0..256).filter(|i| map.determine_map(i) == idx).collect();
+            assert_eq!(keys, expected);
         }
     }
 
     #[test]
-    fn insert_then_iter_1024() {
+    fn contains_keys_half_present() {
         let map = DashMap::default();
 
-        for i in 0..1024_i32 {
-            map.insert(i, i * 2);
+        for i in (0..100_i32).filter(|i| i % 2 == 0) {
+            map.insert(i, i);
         }
 
-        map.alter_all(|v| v * 2);
+        let keys: Vec<i32> = (0..100).collect();
+        let expected: Vec<bool> = keys.iter().map(|k| map.contains_key(k)).collect();
 
-        assert_eq!(map.iter().count(), 1024);
+        assert_eq!(map.contains_keys(&keys), expected);
+        assert_eq!(expected.iter().filter(|b| **b).count(), 50);
     }
 
     #[test]
-    fn insert_then_iter_mut_map_1024() {
+    fn get_backoff_under_contention() {
         let map = DashMap::default();
+        map.insert(1, 42);
+        let barrier = std::sync::Barrier::new(2);
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let _r = map.index_mut(&1);
+                barrier.wait();
+                std::thread::sleep(Duration::from_millis(20));
+            });
+
+            barrier.wait();
+            assert!(map.get_backoff(&1, 3).is_err());
+            assert_eq!(*map.get_backoff(&1, usize::MAX).ok().unwrap(), 42);
+        });
 
-        for i in 0..1024_i32 {
-            map.insert(i, 4);
+        assert!(map.get_backoff(&2, 8).is_err());
+    }
+
+    #[test]
+    fn diff_keys_two_maps() {
+        let a = DashMap::default();
+        let b = DashMap::default();
+
+        for i in 0..100_i32 {
+            a.insert(i, ());
         }
 
-        map.iter_mut().for_each(|mut r| *r *= 2);
+        for i in 50..150_i32 {
+            b.insert(i, ());
+        }
 
-        assert_eq!(map.iter().fold(0, |acc, e| acc + *e), 8192);
+        let (mut only_a, mut only_b) = diff_keys(&a, &b);
+        only_a.sort();
+        only_b.sort();
+
+        assert_eq!(only_a, (0..50).collect::<Vec<_>>());
+        assert_eq!(only_b, (100..150).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rebalance_skewed() {
+        let mut map = DashMap::new(4);
+
+        {
+            let mut first = map.submaps[0].write();
+            for i in 0..1024_i32 {
+                first.insert(i, i * 2);
+            }
+        }
+
+        map.rebalance();
+
+        let loads: Vec<usize> = (0..map.chunks_count())
+            .map(|idx| map.shard_keys(idx).len())
+            .collect();
+
+        assert_eq!(loads.iter().sum::<usize>(), 1024);
+        assert!(loads.iter().all(|load| *load < 256));
+
+        for i in 0..1024_i32 {
+            assert_eq!(i * 2, *map.get(&i).unwrap());
+        }
     }
 
     #[test]
@@ -958,4 +3417,14 @@ mod tests {
         map.insert("foo".to_string(), 51i32);
         assert_eq!(*map.index("foo"), 51i32);
     }
+
+    #[test]
+    fn send_sync() {
+        assert_send_sync::<DashMap<String, Vec<u8>>>();
+        assert_send_sync::<DashMapRef<String, Vec<u8>>>();
+        assert_send_sync::<DashMapRefMut<String, Vec<u8>>>();
+        assert_send_sync::<DashMapIterRef<String, Vec<u8>>>();
+        assert_send_sync::<Iter<String, Vec<u8>>>();
+        assert_send_sync::<IterOwned<String, Vec<u8>>>();
+    }
 }
@@ -0,0 +1,201 @@
+//! Please see the struct level documentation.
+
+use owning_ref::{OwningRef, OwningRefMut};
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::ops::{Deref, DerefMut};
+
+const SEGMENT_SIZE: usize = 64;
+
+type Segment<V> = Box<[RwLock<Option<V>>]>;
+
+/// DenseConcurrentMap is a threadsafe concurrent map for dense integer keys.
+///
+/// Keys index directly into slots instead of being hashed which makes it a good fit
+/// for keys in the range 0..N. Every slot is individually locked.
+///
+/// Slots are stored in fixed size segments that are never moved or freed while the map is alive.
+/// Growing the map appends new segments under a resize lock which only has to be held briefly
+/// by other operations to locate a slot.
+pub struct DenseConcurrentMap<V> {
+    segments: RwLock<Vec<Segment<V>>>,
+}
+
+impl<'a, V: 'a> DenseConcurrentMap<V> {
+    /// Create a new, empty map.
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Create a new map with room for at least `capacity` keys before needing to grow.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let segment_count = capacity.div_ceil(SEGMENT_SIZE);
+
+        Self {
+            segments: RwLock::new((0..segment_count).map(|_| Self::new_segment()).collect()),
+        }
+    }
+
+    /// Insert an element into the map, growing it if needed. Returns the previous value if one existed.
+    #[inline]
+    pub fn insert(&self, index: usize, value: V) -> Option<V> {
+        let slot = match self.slot(index) {
+            Some(slot) => slot,
+            None => self.grow_to(index),
+        };
+
+        slot.write().replace(value)
+    }
+
+    /// Get a shared reference to an element contained within the map.
+    #[inline]
+    pub fn get(&'a self, index: usize) -> Option<DenseMapRef<'a, V>> {
+        let slot = self.slot(index)?;
+        let or = OwningRef::new(slot.read());
+        or.try_map(|v| v.as_ref().ok_or(()))
+            .ok()
+            .map(|ptr| DenseMapRef { ptr })
+    }
+
+    /// Get a unique reference to an element contained within the map.
+    #[inline]
+    pub fn get_mut(&'a self, index: usize) -> Option<DenseMapRefMut<'a, V>> {
+        let slot = self.slot(index)?;
+        let or = OwningRefMut::new(slot.write());
+        or.try_map_mut(|v| v.as_mut().ok_or(()))
+            .ok()
+            .map(|ptr| DenseMapRefMut { ptr })
+    }
+
+    /// Remove an element from the map if it exists.
+    #[inline]
+    pub fn remove(&self, index: usize) -> Option<V> {
+        self.slot(index)?.write().take()
+    }
+
+    /// Check if the map contains the specified key.
+    #[inline]
+    pub fn contains_key(&self, index: usize) -> bool {
+        self.slot(index).is_some_and(|slot| slot.read().is_some())
+    }
+
+    /// Get the amount of keys the map can hold without growing.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.segments.read().len() * SEGMENT_SIZE
+    }
+
+    #[inline]
+    fn new_segment() -> Segment<V> {
+        (0..SEGMENT_SIZE)
+            .map(|_| RwLock::new(None))
+            .collect::<Vec<_>>()
+            .into_boxed_slice()
+    }
+
+    #[inline]
+    fn slot(&self, index: usize) -> Option<&RwLock<Option<V>>> {
+        let segments = self.segments.read();
+        let slot = segments
+            .get(index / SEGMENT_SIZE)
+            .map(|segment| &segment[index % SEGMENT_SIZE])?;
+
+        // Segments are boxed and never freed until the map is dropped so the slot outlives the resize lock.
+        Some(unsafe { &*(slot as *const _) })
+    }
+
+    fn grow_to(&self, index: usize) -> &RwLock<Option<V>> {
+        let mut segments = self.segments.write();
+
+        while segments.len() <= index / SEGMENT_SIZE {
+            segments.push(Self::new_segment());
+        }
+
+        let slot = &segments[index / SEGMENT_SIZE][index % SEGMENT_SIZE];
+        unsafe { &*(slot as *const _) }
+    }
+}
+
+impl<V> Default for DenseConcurrentMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A shared reference into a DenseConcurrentMap.
+pub struct DenseMapRef<'a, V> {
+    ptr: OwningRef<RwLockReadGuard<'a, Option<V>>, V>,
+}
+
+impl<'a, V> Deref for DenseMapRef<'a, V> {
+    type Target = V;
+
+    #[inline]
+    fn deref(&self) -> &V {
+        &self.ptr
+    }
+}
+
+/// A unique reference into a DenseConcurrentMap.
+pub struct DenseMapRefMut<'a, V> {
+    ptr: OwningRefMut<RwLockWriteGuard<'a, Option<V>>, V>,
+}
+
+impl<'a, V> Deref for DenseMapRefMut<'a, V> {
+    type Target = V;
+
+    #[inline]
+    fn deref(&self) -> &V {
+        &self.ptr
+    }
+}
+
+impl<'a, V> DerefMut for DenseMapRefMut<'a, V> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut V {
+        &mut self.ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{assert_send_sync, assert_sync};
+    use rayon::prelude::*;
+
+    #[test]
+    fn insert_then_assert_rayon_grow() {
+        let map = DenseConcurrentMap::with_capacity(16);
+
+        (0..1000_usize).into_par_iter().for_each(|i| {
+            map.insert(i, i * 3);
+        });
+
+        assert!(map.capacity() >= 1000);
+
+        for i in 0..1000_usize {
+            assert_eq!(i * 3, *map.get(i).unwrap());
+        }
+
+        assert!(map.get(1000).is_none());
+        assert!(map.get(100_000).is_none());
+    }
+
+    #[test]
+    fn get_mut_then_remove() {
+        let map = DenseConcurrentMap::new();
+        map.insert(7, 2_i32);
+        *map.get_mut(7).unwrap() *= 21;
+        assert_eq!(*map.get(7).unwrap(), 42);
+        assert_eq!(map.remove(7), Some(42));
+        assert!(!map.contains_key(7));
+        assert!(map.get_mut(7).is_none());
+    }
+
+    #[test]
+    fn send_sync() {
+        assert_send_sync::<DenseConcurrentMap<Vec<u8>>>();
+        // References hold a lock guard which must be released on the thread that acquired it.
+        assert_sync::<DenseMapRef<Vec<u8>>>();
+        assert_sync::<DenseMapRefMut<Vec<u8>>>();
+    }
+}
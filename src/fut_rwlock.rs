@@ -1,6 +1,7 @@
 use parking_lot::Mutex as RegularMutex;
 use parking_lot::RwLock as RegularRwLock;
 use parking_lot::RwLockReadGuard as RegularRwLockReadGuard;
+use parking_lot::RwLockUpgradableReadGuard as RegularRwLockUpgradableReadGuard;
 use parking_lot::RwLockWriteGuard as RegularRwLockWriteGuard;
 use slab::Slab;
 use stable_deref_trait::StableDeref;
@@ -83,6 +84,18 @@ impl<'a, T> DerefMut for RwLockWriteGuard<'a, T> {
     }
 }
 
+impl<'a, T> RwLockWriteGuard<'a, T> {
+    #[inline]
+    pub fn downgrade(mut s: Self) -> RwLockReadGuard<'a, T> {
+        let inner = s._inner_guard.take().expect("write guard already released");
+
+        RwLockReadGuard {
+            _inner_guard: Some(RegularRwLockWriteGuard::downgrade(inner)),
+            lock: s.lock,
+        }
+    }
+}
+
 impl<'a, T> Drop for RwLockWriteGuard<'a, T> {
     #[inline]
     fn drop(&mut self) {
@@ -94,6 +107,46 @@ impl<'a, T> Drop for RwLockWriteGuard<'a, T> {
     }
 }
 
+pub struct RwLockUpgradableReadGuard<'a, T> {
+    _inner_guard: Option<RegularRwLockUpgradableReadGuard<'a, ()>>,
+    lock: &'a RwLock<T>,
+}
+
+impl<'a, T> RwLockUpgradableReadGuard<'a, T> {
+    #[inline]
+    pub fn upgrade(mut s: Self) -> RwLockWriteGuard<'a, T> {
+        let inner = s
+            ._inner_guard
+            .take()
+            .expect("upgradable guard already released");
+
+        RwLockWriteGuard {
+            _inner_guard: Some(RegularRwLockUpgradableReadGuard::upgrade(inner)),
+            lock: s.lock,
+        }
+    }
+}
+
+impl<'a, T> Deref for RwLockUpgradableReadGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for RwLockUpgradableReadGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        drop(self._inner_guard.take());
+        let mut waiters = self.lock.waiters.lock();
+        if let Some((_i, waiter)) = waiters.iter_mut().next() {
+            waiter.wake();
+        }
+    }
+}
+
 pub struct RwLock<T> {
     lock: RegularRwLock<()>,
     waiters: RegularMutex<Slab<Waiter>>,
@@ -128,6 +181,11 @@ impl<T> RwLock<T> {
         }
     }
 
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
     #[inline]
     pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
         self.lock.try_read().map(|guard| RwLockReadGuard {
@@ -176,6 +234,14 @@ impl<T> RwLock<T> {
         }
     }
 
+    #[inline]
+    pub fn upgradable_read(&self) -> RwLockUpgradableReadGuard<'_, T> {
+        RwLockUpgradableReadGuard {
+            _inner_guard: Some(self.lock.upgradable_read()),
+            lock: self,
+        }
+    }
+
     #[inline]
     pub fn async_read(&self) -> RwLockReadFuture<'_, T> {
         RwLockReadFuture {
@@ -284,7 +350,7 @@ impl<'a, T> Future for RwLockWriteFuture<'a, T> {
 }
 
 unsafe impl<T: Send> Send for RwLock<T> {}
-unsafe impl<T: Sync> Sync for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
 
 unsafe impl<T: Send> Send for RwLockReadFuture<'_, T> {}
 unsafe impl<T: Send> Sync for RwLockReadFuture<'_, T> {}
@@ -292,11 +358,15 @@ unsafe impl<T: Send> Sync for RwLockReadFuture<'_, T> {}
 unsafe impl<T: Send> Send for RwLockWriteFuture<'_, T> {}
 unsafe impl<T: Send> Sync for RwLockWriteFuture<'_, T> {}
 
-unsafe impl<T: Send> Send for RwLockReadGuard<'_, T> {}
+unsafe impl<T: Sync> Send for RwLockReadGuard<'_, T> {}
 unsafe impl<T: Sync> Sync for RwLockReadGuard<'_, T> {}
 
-unsafe impl<T: Send> Send for RwLockWriteGuard<'_, T> {}
+unsafe impl<T: Send + Sync> Send for RwLockWriteGuard<'_, T> {}
 unsafe impl<T: Sync> Sync for RwLockWriteGuard<'_, T> {}
 
+unsafe impl<T: Send + Sync> Send for RwLockUpgradableReadGuard<'_, T> {}
+unsafe impl<T: Sync> Sync for RwLockUpgradableReadGuard<'_, T> {}
+
 unsafe impl<T> StableDeref for RwLockReadGuard<'_, T> {}
 unsafe impl<T> StableDeref for RwLockWriteGuard<'_, T> {}
+unsafe impl<T> StableDeref for RwLockUpgradableReadGuard<'_, T> {}
@@ -0,0 +1,160 @@
+//! Please see the struct level documentation.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Amount of linear sub-buckets every power of two is split into.
+const SUB_BUCKETS: usize = 8;
+const SUB_BUCKET_BITS: u32 = 3;
+const BUCKET_COUNT: usize = SUB_BUCKETS + (64 - SUB_BUCKET_BITS as usize) * SUB_BUCKETS;
+
+/// ConcurrentHistogram is a threadsafe and lockfree histogram for tracking distributions such as latencies.
+///
+/// Values are counted in fixed logarithmic buckets. Every power of two range is split into 8 linear
+/// sub-buckets which bounds the relative error of a reported percentile to 12.5%.
+/// Recording a value is a single atomic increment.
+pub struct ConcurrentHistogram {
+    buckets: Box<[AtomicU64]>,
+}
+
+impl ConcurrentHistogram {
+    /// Create a new, empty histogram.
+    pub fn new() -> Self {
+        Self {
+            buckets: (0..BUCKET_COUNT)
+                .map(|_| AtomicU64::new(0))
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
+        }
+    }
+
+    /// Record a value.
+    #[inline]
+    pub fn record(&self, value: u64) {
+        self.buckets[bucket_index(value)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Get the amount of recorded values.
+    #[inline]
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).sum()
+    }
+
+    /// Get an upper bound for the value below which `p` percent of the recorded values fall.
+    /// Returns 0 if nothing has been recorded.
+    ///
+    /// Will panic if `p` is not within 0 to 100.
+    pub fn percentile(&self, p: f64) -> u64 {
+        assert!(
+            (0.0..=100.0).contains(&p),
+            "percentile must be within 0 to 100"
+        );
+
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|b| b.load(Ordering::Relaxed))
+            .collect();
+        let total: u64 = counts.iter().sum();
+
+        if total == 0 {
+            return 0;
+        }
+
+        let rank = ((p / 100.0 * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+
+        for (idx, count) in counts.iter().enumerate() {
+            seen += count;
+
+            if seen >= rank {
+                return bucket_upper_bound(idx);
+            }
+        }
+
+        bucket_upper_bound(BUCKET_COUNT - 1)
+    }
+
+    /// Reset all buckets to zero.
+    #[inline]
+    pub fn clear(&self) {
+        self.buckets
+            .iter()
+            .for_each(|b| b.store(0, Ordering::Relaxed));
+    }
+}
+
+impl Default for ConcurrentHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline]
+fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKETS as u64 {
+        return value as usize;
+    }
+
+    let msb = 63 - value.leading_zeros();
+    let shift = msb - SUB_BUCKET_BITS;
+    let sub = (value >> shift) as usize - SUB_BUCKETS;
+
+    SUB_BUCKETS + shift as usize * SUB_BUCKETS + sub
+}
+
+#[inline]
+fn bucket_upper_bound(idx: usize) -> u64 {
+    if idx < SUB_BUCKETS {
+        return idx as u64;
+    }
+
+    let shift = ((idx - SUB_BUCKETS) / SUB_BUCKETS) as u32;
+    let sub = ((idx - SUB_BUCKETS) % SUB_BUCKETS) as u64;
+    let lower = (SUB_BUCKETS as u64 + sub) << shift;
+
+    lower + ((1 << shift) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::assert_send_sync;
+    use rayon::prelude::*;
+
+    #[test]
+    fn bucket_bounds_contain_value() {
+        for value in (0..100_000_u64).chain(vec![u64::MAX - 1, u64::MAX]) {
+            let idx = bucket_index(value);
+            assert!(idx < BUCKET_COUNT);
+            assert!(bucket_upper_bound(idx) >= value);
+            assert!(idx == 0 || bucket_upper_bound(idx - 1) < value);
+        }
+    }
+
+    #[test]
+    fn percentiles_rayon() {
+        let histogram = ConcurrentHistogram::new();
+
+        (0..8).into_par_iter().for_each(|_| {
+            for value in 1..=1000_u64 {
+                histogram.record(value);
+            }
+        });
+
+        assert_eq!(histogram.count(), 8000);
+
+        let p50 = histogram.percentile(50.0);
+        let p99 = histogram.percentile(99.0);
+        assert!((500..570).contains(&p50), "p50 was {}", p50);
+        assert!((990..1120).contains(&p99), "p99 was {}", p99);
+        assert_eq!(histogram.percentile(100.0), 1023);
+
+        histogram.clear();
+        assert_eq!(histogram.percentile(50.0), 0);
+    }
+
+    #[test]
+    fn send_sync() {
+        assert_send_sync::<ConcurrentHistogram>();
+    }
+}
@@ -0,0 +1,108 @@
+//! Please see the struct level documentation.
+
+use crate::dashmap::DashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Interner is a threadsafe concurrent interner handing out stable ids for values.
+///
+/// Interning the same value from multiple threads at the same time is guaranteed to produce the same id
+/// since new ids are allocated while holding the write lock of the shard the value belongs to.
+pub struct Interner<T>
+where
+    T: Hash + Eq + Clone,
+{
+    ids: DashMap<T, u32>,
+    values: DashMap<u32, T>,
+    next_id: AtomicU32,
+}
+
+impl<T> Interner<T>
+where
+    T: Hash + Eq + Clone,
+{
+    /// Create a new, empty interner.
+    pub fn new() -> Self {
+        Self {
+            ids: DashMap::default(),
+            values: DashMap::default(),
+            next_id: AtomicU32::new(0),
+        }
+    }
+
+    /// Intern a value, returning the id associated with it. Allocates a new id if the value has not been seen before.
+    #[inline]
+    pub fn intern(&self, value: T) -> u32 {
+        let id = self.ids.get_or_insert_with(&value, || {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            self.values.insert(id, value.clone());
+            id
+        });
+
+        *id
+    }
+
+    /// Get the value associated with an id.
+    #[inline]
+    pub fn resolve(&self, id: u32) -> Option<T> {
+        self.values.get(&id).map(|v| v.clone())
+    }
+
+    /// Get the amount of interned values.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Check if the interner is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Default for Interner<T>
+where
+    T: Hash + Eq + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::assert_send_sync;
+    use rayon::prelude::*;
+
+    #[test]
+    fn intern_then_resolve() {
+        let interner = Interner::new();
+        let foo = interner.intern("foo".to_string());
+        let bar = interner.intern("bar".to_string());
+
+        assert_ne!(foo, bar);
+        assert_eq!(interner.intern("foo".to_string()), foo);
+        assert_eq!(interner.resolve(bar).unwrap(), "bar");
+        assert_eq!(interner.resolve(bar + foo + 1), None);
+    }
+
+    #[test]
+    fn intern_same_rayon() {
+        let interner = Interner::new();
+
+        let ids: Vec<u32> = (0..1024)
+            .into_par_iter()
+            .map(|_| interner.intern("wokeblox".to_string()))
+            .collect();
+
+        assert!(ids.iter().all(|id| *id == ids[0]));
+        assert_eq!(interner.len(), 1);
+    }
+
+    #[test]
+    fn send_sync() {
+        assert_send_sync::<Interner<String>>();
+    }
+}
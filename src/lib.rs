@@ -2,9 +2,16 @@
 //!
 //! Please read the module documentation for a given module before using it
 
+pub mod btreemap;
 pub mod dashmap;
+pub mod densemap;
 mod fut_rwlock;
+pub mod histogram;
+pub mod interner;
+pub mod multimap;
 pub mod nestedmap;
+pub mod register;
+pub mod ringbuffer;
 pub mod stack;
 pub mod timedcache;
 mod uniform_allocator;
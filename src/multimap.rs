@@ -0,0 +1,134 @@
+//! Please see the struct level documentation.
+
+use crate::dashmap::DashMap;
+use std::hash::Hash;
+
+/// MultiMap is a threadsafe concurrent map associating every key with multiple values.
+///
+/// It is backed by a DashMap storing a vector of values per key. Keys without values are removed.
+pub struct MultiMap<K, V>
+where
+    K: Hash + Eq,
+{
+    map: DashMap<K, Vec<V>>,
+}
+
+impl<K, V> MultiMap<K, V>
+where
+    K: Hash + Eq,
+{
+    /// Create a new, empty multimap.
+    pub fn new() -> Self {
+        Self {
+            map: DashMap::default(),
+        }
+    }
+
+    /// Append a value to the values associated with a key.
+    #[inline]
+    pub fn insert(&self, k: K, v: V) {
+        let mut submap = self.map.get_raw_mut_from_key(&k);
+        submap.entry(k).or_default().push(v);
+    }
+
+    /// Clone all values associated with a key.
+    #[inline]
+    pub fn get_all(&self, k: &K) -> Option<Vec<V>>
+    where
+        V: Clone,
+    {
+        self.map.get(k).map(|values| values.clone())
+    }
+
+    /// Remove one value equal to the given value from the values associated with a key.
+    /// Returns whether a value was removed.
+    #[inline]
+    pub fn remove_value(&self, k: &K, v: &V) -> bool
+    where
+        V: PartialEq,
+    {
+        let mut submap = self.map.get_raw_mut_from_key(k);
+
+        let (removed, now_empty) = match submap.get_mut(k) {
+            Some(values) => match values.iter().position(|value| value == v) {
+                Some(idx) => {
+                    values.swap_remove(idx);
+                    (true, values.is_empty())
+                }
+                None => (false, false),
+            },
+            None => (false, false),
+        };
+
+        if now_empty {
+            submap.remove(k);
+        }
+
+        removed
+    }
+
+    /// Get the total amount of values stored for all keys.
+    #[inline]
+    pub fn len_values(&self) -> usize {
+        let mut count = 0;
+        self.map.for_each(|_, values| count += values.len());
+        count
+    }
+}
+
+impl<K, V> Default for MultiMap<K, V>
+where
+    K: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::assert_send_sync;
+    use rayon::prelude::*;
+
+    #[test]
+    fn insert_rayon_then_get_all() {
+        let map = MultiMap::new();
+
+        (0..1024_i32).into_par_iter().for_each(|i| {
+            map.insert(i % 8, i);
+        });
+
+        assert_eq!(map.len_values(), 1024);
+
+        for k in 0..8 {
+            let mut values = map.get_all(&k).unwrap();
+            values.sort();
+            assert_eq!(values, (0..1024).filter(|i| i % 8 == k).collect::<Vec<_>>());
+        }
+
+        assert_eq!(map.get_all(&8), None);
+    }
+
+    #[test]
+    fn remove_value_drops_empty_key() {
+        let map = MultiMap::new();
+        map.insert("foo", 1_i32);
+        map.insert("foo", 1);
+        map.insert("foo", 2);
+
+        assert!(map.remove_value(&"foo", &1));
+        assert!(!map.remove_value(&"foo", &3));
+        assert_eq!(map.len_values(), 2);
+
+        assert!(map.remove_value(&"foo", &1));
+        assert!(map.remove_value(&"foo", &2));
+        assert_eq!(map.get_all(&"foo"), None);
+        assert!(!map.remove_value(&"bar", &1));
+    }
+
+    #[test]
+    fn send_sync() {
+        assert_send_sync::<MultiMap<String, Vec<u8>>>();
+    }
+}
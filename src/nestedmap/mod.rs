@@ -14,6 +14,7 @@ pub use raw::{TableIter, TableRef};
 use std::fmt;
 use std::hash::Hash;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 pub struct OccupiedEntry<'a, K: Hash + Eq, V> {
@@ -176,6 +177,8 @@ impl<'a, K: Hash + Eq + Clone, V> Entry<'a, K, V> {
     }
 }
 
+pub use crate::util::with_guard;
+
 #[inline(always)]
 pub fn aquire_guard() -> Guard {
     epoch::pin()
@@ -188,13 +191,18 @@ pub fn aquire_guard() -> Guard {
 /// makes it more appealing for latency critical things. It also has faster reads that DHashMap.
 pub struct NestedMap<K: Hash + Eq, V> {
     root: Table<K, V>,
+    len: AtomicUsize,
 }
 
 impl<'a, K: 'a + Hash + Eq, V: 'a> NestedMap<K, V> {
     /// Create a new completely empty map.
+    ///
+    /// The root table is prefilled with empty branch tables. This reduces branch promotion during initial growth
+    /// at the cost of a higher baseline memory usage.
     pub fn new() -> Self {
         Self {
             root: Table::layer_pregen(Arc::new(UniformAllocator::default()), 1),
+            len: AtomicUsize::new(0),
         }
     }
 
@@ -204,6 +212,7 @@ impl<'a, K: 'a + Hash + Eq, V: 'a> NestedMap<K, V> {
     pub fn new_layer_prefill() -> Self {
         Self {
             root: Table::layer_pregen(Arc::new(UniformAllocator::default()), 1),
+            len: AtomicUsize::new(0),
         }
     }
 
@@ -224,7 +233,38 @@ impl<'a, K: 'a + Hash + Eq, V: 'a> NestedMap<K, V> {
             tag as usize,
             Bucket::Leaf(tag, RawEntry { key, value }),
         );
-        self.root.insert(bucket, guard);
+
+        if self.root.insert(bucket, guard) {
+            self.len.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Get a reference to the value of a key, inserting `default` first if the key is not present.
+    /// An existing value is never replaced.
+    #[inline]
+    pub fn get_or_insert(&'a self, key: K, default: V) -> TableRef<'a, K, V> {
+        let guard = epoch::pin();
+        let tag: u8 = rand::thread_rng().gen();
+
+        let bucket = Owned::uniform_alloc(
+            self.root.allocator(),
+            tag as usize,
+            Bucket::Leaf(
+                tag,
+                RawEntry {
+                    key,
+                    value: default,
+                },
+            ),
+        );
+
+        let (r, inserted) = self.root.get_or_insert(bucket, guard);
+
+        if inserted {
+            self.len.fetch_add(1, Ordering::Relaxed);
+        }
+
+        r
     }
 
     /// Get a reference to a value in the map.
@@ -240,6 +280,20 @@ impl<'a, K: 'a + Hash + Eq, V: 'a> NestedMap<K, V> {
         self.root.get(key, guard)
     }
 
+    /// Replace the value of an item with the value computed from the current one without removing it.
+    /// Returns whether the key was found.
+    ///
+    /// The new value is swapped in atomically. If another thread modifies the item in the meantime
+    /// `f` is called again with the newer value, so it should not have side effects.
+    #[inline(always)]
+    pub fn update<F: FnMut(&V) -> V>(&self, key: &K, mut f: F) -> bool
+    where
+        K: Clone,
+    {
+        let guard = &epoch::pin();
+        self.root.update(key, &mut f, guard)
+    }
+
     /// Remove an item from the map.
     #[inline(always)]
     pub fn remove(&self, key: &K) {
@@ -250,7 +304,9 @@ impl<'a, K: 'a + Hash + Eq, V: 'a> NestedMap<K, V> {
     /// Remove an item from the map with an existing guard, saving on guard creation.
     #[inline(always)]
     pub fn remove_with_guard(&self, key: &K, guard: &Guard) {
-        self.root.remove(key, guard);
+        if self.root.remove(key, guard) {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+        }
     }
 
     /// Check if the map contains a given key.
@@ -261,6 +317,7 @@ impl<'a, K: 'a + Hash + Eq, V: 'a> NestedMap<K, V> {
     }
 
     /// Iterate over all items in a map.
+    /// The iterator and the references it yields hold an epoch guard and can't be sent to other threads.
     #[inline(always)]
     pub fn iter(&'a self) -> TableIter<'a, K, V> {
         let guard = Rc::new(epoch::pin());
@@ -274,12 +331,37 @@ impl<'a, K: 'a + Hash + Eq, V: 'a> NestedMap<K, V> {
         self.root.len(guard)
     }
 
+    /// Get the amount of elements in the map from a counter maintained by inserts and removals.
+    /// This is much cheaper than `len` which walks the whole map, but may be off while
+    /// concurrent modifications are in progress.
+    #[inline(always)]
+    pub fn approx_len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
     /// Check if the map is empty.
     #[inline(always)]
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
+    /// Shrink the map after removals by replacing every branch table holding a single entry with the entry itself.
+    /// This saves memory and an indirection on lookups of the hoisted entries.
+    ///
+    /// Requires exclusive access since concurrent operations may be traversing the branches being replaced.
+    #[inline]
+    pub fn compact(&mut self) {
+        self.root.compact();
+    }
+
+    /// Apply a function to every value in the map in place.
+    ///
+    /// Requires exclusive access since concurrent operations may be reading the values being modified.
+    #[inline]
+    pub fn for_each_mut<F: FnMut(&K, &mut V)>(&mut self, mut f: F) {
+        self.root.for_each_mut(&mut f);
+    }
+
     /// Get an entry from the map.
     #[inline]
     pub fn entry(&'a self, key: K) -> Entry<'a, K, V> {
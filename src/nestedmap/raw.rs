@@ -35,6 +35,15 @@ impl<K: Hash + Eq, V> Bucket<K, V> {
         }
     }
 
+    #[inline(always)]
+    fn entry_ref(&self) -> &Entry<K, V> {
+        if let Bucket::Leaf(_, entry) = self {
+            entry
+        } else {
+            panic!("bucket unvalid entry get")
+        }
+    }
+
     #[inline(always)]
     fn tag(&self) -> u8 {
         match self {
@@ -137,6 +146,24 @@ impl<'a, K: 'a + Hash + Eq, V: 'a> Table<K, V> {
         table
     }
 
+    /// Free a branch created by `with_two_entries` that lost the race to be published.
+    /// The leaves it points to are still owned by their callers and are left untouched.
+    unsafe fn free_unpublished_branch(&self, branch: Shared<'_, Bucket<K, V>>) {
+        let tag = branch.deref().tag() as usize;
+
+        if let Some(Bucket::Branch(_, table)) = branch.uniform_dealloc(&self.allocator, tag) {
+            for bucket in table.buckets.iter() {
+                let ptr = bucket.load(Ordering::Relaxed, epoch::unprotected());
+
+                if let Some(Bucket::Branch(_, _)) = ptr.as_ref() {
+                    self.free_unpublished_branch(ptr);
+                }
+
+                bucket.store(sharedptr_null(), Ordering::Relaxed);
+            }
+        }
+    }
+
     #[inline]
     pub fn empty(allocator: Arc<UniformAllocator<Bucket<K, V>>>) -> Self {
         Self {
@@ -197,8 +224,10 @@ impl<'a, K: 'a + Hash + Eq, V: 'a> Table<K, V> {
         self.get(key, guard).is_some()
     }
 
+    /// Insert a leaf, replacing the leaf with the same key if one exists.
+    /// Returns whether the key was not present before.
     #[inline]
-    pub fn insert(&self, entry: Owned<Bucket<K, V>>, guard: &Guard) {
+    pub fn insert(&self, entry: Owned<Bucket<K, V>>, guard: &Guard) -> bool {
         let key_pos = util::hash_with_nonce(entry.key_ref(), self.nonce) as usize % TABLE_SIZE;
         let bucket = &self.buckets[key_pos];
 
@@ -210,7 +239,7 @@ impl<'a, K: 'a + Hash + Eq, V: 'a> Table<K, V> {
             Ordering::Acquire,
             guard,
         ) {
-            Ok(_) => {}
+            Ok(_) => true,
 
             Err(err) => {
                 entry = Some(err.new);
@@ -222,11 +251,20 @@ impl<'a, K: 'a + Hash + Eq, V: 'a> Table<K, V> {
                     Bucket::Branch(_, ref table) => table.insert(entry, guard),
                     Bucket::Leaf(actual_tag, ref old_entry) => {
                         if entry.key_ref() == &old_entry.key {
-                            bucket.store(entry, Ordering::Release);
-                            unsafe {
-                                guard.defer_unchecked(|| {
-                                    actual.uniform_dealloc(&self.allocator, *actual_tag as usize);
-                                })
+                            match bucket.compare_and_set(actual, entry, Ordering::SeqCst, guard) {
+                                Ok(_) => {
+                                    let allocator = self.allocator.clone();
+                                    let tag = *actual_tag as usize;
+
+                                    unsafe {
+                                        guard.defer_unchecked(move || {
+                                            actual.uniform_dealloc(&allocator, tag);
+                                        })
+                                    };
+
+                                    false
+                                }
+                                Err(err) => self.insert(err.new, guard),
                             }
                         } else {
                             let tag: u8 = rand::thread_rng().gen();
@@ -247,8 +285,11 @@ impl<'a, K: 'a + Hash + Eq, V: 'a> Table<K, V> {
                             //bucket.store(new_table, Ordering::Release);
                             match bucket.compare_and_set(actual, new_table, Ordering::SeqCst, guard)
                             {
-                                Ok(_) => {}
-                                Err(_) => self.insert(unsafe { Owned::from_usize(uz) }, guard),
+                                Ok(_) => true,
+                                Err(err) => unsafe {
+                                    self.free_unpublished_branch(err.new.into_shared(guard));
+                                    self.insert(Owned::from_usize(uz), guard)
+                                },
                             }
                         }
                     }
@@ -257,8 +298,152 @@ impl<'a, K: 'a + Hash + Eq, V: 'a> Table<K, V> {
         }
     }
 
+    /// Insert a leaf unless its key is already present and return a reference to the resident leaf
+    /// together with whether it was inserted.
     #[inline]
-    pub fn remove(&self, key: &K, guard: &Guard) {
+    pub fn get_or_insert(
+        &'a self,
+        entry: Owned<Bucket<K, V>>,
+        guard: Guard,
+    ) -> (TableRef<'a, K, V>, bool) {
+        let fake_guard = unsafe { epoch::unprotected() };
+        let key_pos = util::hash_with_nonce(entry.key_ref(), self.nonce) as usize % TABLE_SIZE;
+        let bucket = &self.buckets[key_pos];
+        let mut entry = entry;
+
+        loop {
+            let actual: Shared<'a, Bucket<K, V>> = bucket.load(Ordering::Acquire, fake_guard);
+
+            match unsafe { actual.as_ref() } {
+                None => match bucket.compare_and_set(actual, entry, Ordering::SeqCst, fake_guard) {
+                    Ok(new) => {
+                        let ptr = unsafe { new.deref() }.entry_ref();
+                        return (
+                            TableRef {
+                                guard: Some(guard),
+                                ptr,
+                            },
+                            true,
+                        );
+                    }
+
+                    Err(err) => entry = err.new,
+                },
+
+                Some(Bucket::Branch(_, table)) => return table.get_or_insert(entry, guard),
+
+                Some(Bucket::Leaf(_, old_entry)) if &old_entry.key == entry.key_ref() => {
+                    // The new leaf was never published so it can be freed right away.
+                    let tag = (*entry).tag() as usize;
+                    entry
+                        .into_shared(fake_guard)
+                        .uniform_dealloc(&self.allocator, tag);
+
+                    return (
+                        TableRef {
+                            guard: Some(guard),
+                            ptr: old_entry,
+                        },
+                        false,
+                    );
+                }
+
+                Some(Bucket::Leaf(_, _)) => {
+                    let tag: u8 = rand::thread_rng().gen();
+                    let entry_shared = entry.into_shared(fake_guard);
+
+                    let new_table = Owned::uniform_alloc(
+                        &self.allocator,
+                        tag as usize,
+                        Bucket::Branch(
+                            tag,
+                            Table::with_two_entries(self.allocator.clone(), actual, entry_shared),
+                        ),
+                    );
+
+                    match bucket.compare_and_set(actual, new_table, Ordering::SeqCst, fake_guard) {
+                        Ok(_) => {
+                            let ptr = unsafe { entry_shared.deref() }.entry_ref();
+                            return (
+                                TableRef {
+                                    guard: Some(guard),
+                                    ptr,
+                                },
+                                true,
+                            );
+                        }
+
+                        Err(err) => unsafe {
+                            self.free_unpublished_branch(err.new.into_shared(fake_guard));
+                            entry = entry_shared.into_owned();
+                        },
+                    }
+                }
+            }
+        }
+    }
+
+    /// Swap the leaf holding `key` for a new leaf with the same tag holding the value computed by `f`.
+    /// `f` is called again with the current value if the slot changed in the meantime.
+    #[inline]
+    pub fn update<F: FnMut(&V) -> V>(&self, key: &K, f: &mut F, guard: &Guard) -> bool
+    where
+        K: Clone,
+    {
+        let key_pos = util::hash_with_nonce(key, self.nonce) as usize % TABLE_SIZE;
+        let bucket = &self.buckets[key_pos];
+
+        loop {
+            let actual = bucket.load(Ordering::Acquire, guard);
+
+            let (tag, old_entry) = match unsafe { actual.as_ref() } {
+                None => return false,
+                Some(Bucket::Branch(_, table)) => return table.update(key, f, guard),
+                Some(Bucket::Leaf(tag, old_entry)) => (*tag, old_entry),
+            };
+
+            if &old_entry.key != key {
+                return false;
+            }
+
+            let new_leaf = Owned::uniform_alloc(
+                &self.allocator,
+                tag as usize,
+                Bucket::Leaf(
+                    tag,
+                    Entry {
+                        key: old_entry.key.clone(),
+                        value: f(&old_entry.value),
+                    },
+                ),
+            );
+
+            match bucket.compare_and_set(actual, new_leaf, Ordering::SeqCst, guard) {
+                Ok(_) => {
+                    let allocator = self.allocator.clone();
+
+                    unsafe {
+                        guard.defer_unchecked(move || {
+                            actual.uniform_dealloc(&allocator, tag as usize);
+                        })
+                    };
+
+                    return true;
+                }
+
+                Err(err) => {
+                    // The new leaf was never published so it can be freed right away.
+                    err.new
+                        .into_shared(guard)
+                        .uniform_dealloc(&self.allocator, tag as usize);
+                }
+            }
+        }
+    }
+
+    /// Remove the leaf with the given key. Returns whether it was removed.
+    #[inline]
+    pub fn remove(&self, key: &K, guard: &Guard) -> bool {
         let key_pos = util::hash_with_nonce(key, self.nonce) as usize % TABLE_SIZE;
 
         let bucket_sharedptr = self.buckets[key_pos].load(Ordering::Acquire, guard);
@@ -266,6 +451,7 @@ impl<'a, K: 'a + Hash + Eq, V: 'a> Table<K, V> {
         if let Some(bucket_ref) = unsafe { bucket_sharedptr.as_ref() } {
             match bucket_ref {
                 Bucket::Branch(_, table) => table.remove(key, guard),
+                Bucket::Leaf(_, entry) if &entry.key != key => false,
                 Bucket::Leaf(tag, _) => {
                     let res = self.buckets[key_pos].compare_and_set(
                         bucket_sharedptr,
@@ -283,11 +469,94 @@ impl<'a, K: 'a + Hash + Eq, V: 'a> Table<K, V> {
                             })
                         };
                     }
+
+                    res.is_ok()
+                }
+            }
+        } else {
+            false
+        }
+    }
+
+    /// Replace every branch holding exactly one leaf with that leaf, bottom up.
+    /// Takes `&mut self` since lookups and inserts may be descending into the replaced branches otherwise.
+    pub fn compact(&mut self) {
+        let guard = unsafe { epoch::unprotected() };
+
+        for idx in 0..TABLE_SIZE {
+            let mut bucket_shared = self.buckets[idx].load(Ordering::Relaxed, guard);
+
+            if bucket_shared.is_null() {
+                continue;
+            }
+
+            if let Bucket::Branch(tag, table) = unsafe { bucket_shared.deref_mut() } {
+                table.compact();
+
+                if let Some(leaf_idx) = table.single_leaf() {
+                    let leaf =
+                        table.buckets[leaf_idx].swap(sharedptr_null(), Ordering::Relaxed, guard);
+                    self.buckets[idx].store(leaf, Ordering::Relaxed);
+
+                    // The branch table is empty now so dropping it does not free the leaf.
+                    bucket_shared.uniform_dealloc(&self.allocator, *tag as usize);
                 }
             }
         }
     }
 
+    /// Apply a function to every value in place.
+    /// Takes `&mut self` since concurrent readers could observe a value while it is being modified otherwise.
+    pub fn for_each_mut<F: FnMut(&K, &mut V)>(&mut self, f: &mut F) {
+        let guard = unsafe { epoch::unprotected() };
+
+        for bucket in self.buckets.iter() {
+            let mut bucket_shared = bucket.load(Ordering::Relaxed, guard);
+
+            if bucket_shared.is_null() {
+                continue;
+            }
+
+            match unsafe { bucket_shared.deref_mut() } {
+                Bucket::Leaf(_, entry) => f(&entry.key, &mut entry.value),
+                Bucket::Branch(_, table) => table.for_each_mut(f),
+            }
+        }
+    }
+
+    #[inline]
+    fn single_leaf(&self) -> Option<usize> {
+        let guard = unsafe { epoch::unprotected() };
+        let mut found = None;
+
+        for idx in 0..TABLE_SIZE {
+            match unsafe { self.buckets[idx].load(Ordering::Relaxed, guard).as_ref() } {
+                None => {}
+                Some(Bucket::Leaf(_, _)) if found.is_none() => found = Some(idx),
+                Some(_) => return None,
+            }
+        }
+
+        found
+    }
+
+    /// Sum of the depths of all leaves, used to observe the shape of the tree in tests.
+    #[cfg(test)]
+    pub fn leaf_depth_sum(&self, depth: usize) -> usize {
+        let guard = unsafe { epoch::unprotected() };
+
+        self.buckets
+            .iter()
+            .map(
+                |bucket| match unsafe { bucket.load(Ordering::Relaxed, guard).as_ref() } {
+                    None => 0,
+                    Some(Bucket::Leaf(_, _)) => depth,
+                    Some(Bucket::Branch(_, table)) => table.leaf_depth_sum(depth + 1),
+                },
+            )
+            .sum()
+    }
+
     #[inline]
     pub fn iter(&'a self, guard: Rc<Guard>) -> TableIter<'a, K, V> {
         TableIter {
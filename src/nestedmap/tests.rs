@@ -1,4 +1,5 @@
 use super::*;
+use crate::util::assert_send_sync;
 use rayon::prelude::*;
 
 #[test]
@@ -24,6 +25,41 @@ fn insert_rayon() {
     });
 }
 
+#[test]
+fn insert_rayon_distinct_all_present() {
+    let map = NestedMap::default();
+
+    (0..100_000_u64).into_par_iter().for_each(|i| {
+        map.insert(i, i * 2);
+    });
+
+    for i in 0..100_000_u64 {
+        assert_eq!(*map.get(&i).expect("key vanished after insert"), i * 2);
+    }
+}
+
+#[test]
+fn update_increments() {
+    let map = NestedMap::default();
+    map.insert("foo", 1_i32);
+
+    assert!(map.update(&"foo", |v| v + 1));
+    assert_eq!(*map.get(&"foo").unwrap(), 2);
+    assert!(!map.update(&"bar", |v| v + 1));
+}
+
+#[test]
+fn update_rayon() {
+    let map = NestedMap::default();
+    map.insert(0_u64, 0_u64);
+
+    (0..10_000).into_par_iter().for_each(|_| {
+        assert!(map.update(&0, |v| v + 1));
+    });
+
+    assert_eq!(*map.get(&0).unwrap(), 10_000);
+}
+
 #[test]
 fn len() {
     let map = NestedMap::default();
@@ -35,6 +71,63 @@ fn len() {
     assert_eq!(map.len(), 1024);
 }
 
+#[test]
+fn approx_len_matches_len() {
+    let map = NestedMap::default();
+
+    (0..10_000_i32).into_par_iter().for_each(|i| {
+        map.insert(i % 5_000, i);
+    });
+
+    (0..10_000_i32).into_par_iter().for_each(|i| {
+        if i % 3 == 0 {
+            map.remove(&i);
+        }
+    });
+
+    assert_eq!(map.approx_len(), map.len());
+    assert_eq!(map.len(), 5_000 - (0..5_000).filter(|i| i % 3 == 0).count());
+}
+
+#[test]
+fn get_or_insert_keeps_first_value() {
+    let map = NestedMap::default();
+
+    assert_eq!(*map.get_or_insert("foo", 1_i32), 1);
+    assert_eq!(*map.get_or_insert("foo", 2), 1);
+    assert_eq!(map.approx_len(), 1);
+}
+
+#[test]
+fn get_or_insert_rayon() {
+    let map = NestedMap::default();
+
+    (0..20_000_u64).into_par_iter().for_each(|i| {
+        let r = map.get_or_insert(i % 1_000, i);
+        assert_eq!(*r % 1_000, i % 1_000);
+    });
+
+    assert_eq!(map.len(), 1_000);
+    assert_eq!(map.approx_len(), 1_000);
+}
+
+#[test]
+fn for_each_mut_doubles() {
+    let mut map = NestedMap::default();
+
+    for i in 0..1024_i32 {
+        map.insert(i, i);
+    }
+
+    map.for_each_mut(|k, v| {
+        assert_eq!(k, v);
+        *v *= 2;
+    });
+
+    assert!((0..1024).all(|i| *map.get(&i).unwrap() == i * 2));
+    assert_eq!(map.len(), 1024);
+}
+
 #[test]
 fn is_empty() {
     let map: NestedMap<i32, i32> = NestedMap::default();
@@ -42,6 +135,17 @@ fn is_empty() {
     assert_eq!(map.is_empty(), true);
 }
 
+#[test]
+#[allow(deprecated)]
+fn new_layer_prefill_behaves_like_new() {
+    let map = NestedMap::new_layer_prefill();
+    assert!(map.is_empty());
+
+    map.insert(1_i32, 2_i32);
+    assert_eq!(*map.get(&1).unwrap(), 2);
+    assert_eq!(map.len(), 1);
+}
+
 #[test]
 fn iter_count_fold() {
     let map = NestedMap::default();
@@ -81,3 +185,40 @@ fn ref_drop_exist() {
     map.remove(&"wokeblox");
     assert_eq!(*r, 492_i32);
 }
+
+#[test]
+fn compact_hoists_single_leaves() {
+    let mut map = NestedMap::default();
+
+    for i in 0..1024_i32 {
+        map.insert(i, i * 3);
+    }
+
+    for i in (0..1024_i32).filter(|i| i % 64 != 0) {
+        map.remove(&i);
+    }
+
+    let depth_before = map.root.leaf_depth_sum(1);
+    map.compact();
+    let depth_after = map.root.leaf_depth_sum(1);
+
+    assert!(depth_after < depth_before);
+    assert_eq!(map.len(), 16);
+
+    for i in 0..1024_i32 {
+        assert_eq!(
+            map.get(&i).map(|r| *r),
+            if i % 64 == 0 { Some(i * 3) } else { None }
+        );
+    }
+
+    map.insert(1, 1);
+    assert_eq!(*map.get(&1).unwrap(), 1);
+    assert_eq!(map.len(), 17);
+}
+
+#[test]
+fn send_sync() {
+    // Iterators and references hold an epoch guard which is tied to the thread that created it.
+    assert_send_sync::<NestedMap<String, Vec<u8>>>();
+}
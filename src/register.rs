@@ -0,0 +1,111 @@
+//! Please see the struct level documentation.
+
+use ccl_crossbeam_epoch::{self as epoch, Atomic, Guard, Owned};
+use std::ops::Deref;
+use std::sync::atomic::Ordering;
+
+/// ConcurrentRegister is a threadsafe and lockfree slot holding a single value where the latest store wins.
+///
+/// Loads never block and always observe a complete value. Replaced values are freed once no reader can
+/// be observing them anymore.
+pub struct ConcurrentRegister<T> {
+    value: Atomic<T>,
+}
+
+impl<T> ConcurrentRegister<T> {
+    /// Create a new register holding a value.
+    pub fn new(value: T) -> Self {
+        Self {
+            value: Atomic::new(value),
+        }
+    }
+
+    /// Replace the value held by the register.
+    #[inline]
+    pub fn store(&self, value: T) {
+        let guard = &epoch::pin();
+        let old = self.value.swap(Owned::new(value), Ordering::AcqRel, guard);
+
+        unsafe {
+            guard.defer_unchecked(move || drop(old.into_owned().into_box()));
+        }
+    }
+
+    /// Get a reference to the current value. The value stays alive for as long as the reference is held,
+    /// even if it is replaced in the meantime.
+    #[inline]
+    pub fn load(&self) -> RegisterRef<'_, T> {
+        let guard = epoch::pin();
+        let ptr = self.value.load(Ordering::Acquire, &guard).as_raw();
+
+        RegisterRef {
+            _guard: guard,
+            ptr: unsafe { &*ptr },
+        }
+    }
+}
+
+impl<T> Drop for ConcurrentRegister<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let ptr = self.value.load(Ordering::Relaxed, epoch::unprotected());
+            drop(ptr.into_owned().into_box());
+        }
+    }
+}
+
+/// A reference to the value of a ConcurrentRegister, keeping it alive while held.
+pub struct RegisterRef<'a, T> {
+    _guard: Guard,
+    ptr: &'a T,
+}
+
+impl<'a, T> Deref for RegisterRef<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::assert_send_sync;
+    use rayon::prelude::*;
+
+    #[test]
+    fn store_then_load() {
+        let register = ConcurrentRegister::new("foo".to_string());
+        let old = register.load();
+        register.store("bar".to_string());
+
+        assert_eq!(*old, "foo");
+        assert_eq!(*register.load(), "bar");
+    }
+
+    #[test]
+    fn writers_and_readers_rayon() {
+        let register = ConcurrentRegister::new((0_usize, 0_usize));
+
+        (0..100_000_usize).into_par_iter().for_each(|i| {
+            if i % 4 == 0 {
+                register.store((i, i * 3));
+            } else {
+                let value = register.load();
+                assert_eq!(value.1, value.0 * 3);
+            }
+        });
+
+        let last = register.load();
+        assert_eq!(last.0 % 4, 0);
+        assert_eq!(last.1, last.0 * 3);
+    }
+
+    #[test]
+    fn send_sync() {
+        // RegisterRef holds an epoch guard which is tied to the thread that created it.
+        assert_send_sync::<ConcurrentRegister<String>>();
+    }
+}
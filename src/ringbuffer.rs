@@ -0,0 +1,181 @@
+//! Please see the struct level documentation.
+
+use ccl_crossbeam_epoch::{self as epoch, Atomic, Owned};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// ConcurrentRingBuffer is a threadsafe and lockfree fixed size buffer keeping the most recently pushed elements.
+///
+/// Pushing to a full buffer overwrites the oldest element. Every element is tagged with the
+/// sequence number it was pushed with so that snapshots stay in push order even while elements are overwritten.
+pub struct ConcurrentRingBuffer<T> {
+    slots: Box<[Atomic<(usize, T)>]>,
+    write_idx: AtomicUsize,
+}
+
+impl<T> ConcurrentRingBuffer<T> {
+    /// Create a new, empty buffer holding at most `capacity` elements.
+    ///
+    /// Will panic if the capacity is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "ring buffer capacity must be non-zero");
+
+        Self {
+            slots: (0..capacity)
+                .map(|_| Atomic::null())
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
+            write_idx: AtomicUsize::new(0),
+        }
+    }
+
+    /// Push an element, overwriting the oldest element if the buffer is full.
+    #[inline]
+    pub fn push(&self, data: T) {
+        let guard = &epoch::pin();
+        let seq = self.write_idx.fetch_add(1, Ordering::AcqRel);
+        let slot = &self.slots[seq % self.slots.len()];
+
+        let mut new = Owned::new((seq, data));
+        let mut current = slot.load(Ordering::Acquire, guard);
+
+        loop {
+            // A writer that lapped us may already have stored a newer element, ours is stale then.
+            if let Some((slot_seq, _)) = unsafe { current.as_ref() } {
+                if *slot_seq > seq {
+                    drop(new.into_box());
+                    return;
+                }
+            }
+
+            match slot.compare_and_set(current, new, Ordering::AcqRel, guard) {
+                Ok(_) => break,
+                Err(err) => {
+                    current = err.current;
+                    new = err.new;
+                }
+            }
+        }
+
+        if !current.is_null() {
+            unsafe {
+                guard.defer_unchecked(move || drop(current.into_owned().into_box()));
+            }
+        }
+    }
+
+    /// Get the maximum amount of elements the buffer can hold.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Get the total amount of elements pushed to the buffer so far.
+    #[inline]
+    pub fn pushed(&self) -> usize {
+        self.write_idx.load(Ordering::Acquire)
+    }
+
+    /// Clone the current contents of the buffer from oldest to newest.
+    ///
+    /// Elements that are overwritten or not yet written while the snapshot is taken are skipped.
+    pub fn snapshot(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let guard = &epoch::pin();
+        let end = self.write_idx.load(Ordering::Acquire);
+        let start = end.saturating_sub(self.slots.len());
+
+        (start..end)
+            .filter_map(|seq| {
+                let slot = self.slots[seq % self.slots.len()].load(Ordering::Acquire, guard);
+
+                match unsafe { slot.as_ref() } {
+                    Some((slot_seq, data)) if *slot_seq == seq => Some(data.clone()),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+impl<T> Drop for ConcurrentRingBuffer<T> {
+    fn drop(&mut self) {
+        let guard = unsafe { epoch::unprotected() };
+
+        for slot in self.slots.iter() {
+            let ptr = slot.load(Ordering::Relaxed, guard);
+
+            if !ptr.is_null() {
+                unsafe {
+                    drop(ptr.into_owned().into_box());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::assert_send_sync;
+    use rayon::prelude::*;
+
+    #[test]
+    fn overwrite_keeps_last_in_order() {
+        let buffer = ConcurrentRingBuffer::new(16);
+
+        for i in 0..32_i32 {
+            buffer.push(i);
+        }
+
+        assert_eq!(buffer.snapshot(), (16..32).collect::<Vec<_>>());
+        assert_eq!(buffer.pushed(), 32);
+    }
+
+    #[test]
+    fn partially_filled() {
+        let buffer = ConcurrentRingBuffer::new(16);
+        buffer.push("foo".to_string());
+        buffer.push("bar".to_string());
+
+        assert_eq!(buffer.snapshot(), vec!["foo", "bar"]);
+    }
+
+    #[test]
+    fn push_rayon() {
+        let buffer = ConcurrentRingBuffer::new(64);
+
+        (0..100_000_i32).into_par_iter().for_each(|i| {
+            buffer.push(i);
+        });
+
+        let mut snapshot = buffer.snapshot();
+        assert_eq!(snapshot.len(), 64);
+        snapshot.sort();
+        snapshot.dedup();
+        assert_eq!(snapshot.len(), 64);
+    }
+
+    #[test]
+    fn push_rayon_keeps_newest() {
+        let buffer = ConcurrentRingBuffer::new(4);
+
+        (0..100_000_usize).into_par_iter().for_each(|i| {
+            buffer.push(i);
+        });
+
+        let guard = &epoch::pin();
+        let end = buffer.pushed();
+
+        for slot in buffer.slots.iter() {
+            let (seq, _) = unsafe { slot.load(Ordering::Acquire, guard).deref() };
+            assert!(end - seq <= buffer.capacity());
+        }
+    }
+
+    #[test]
+    fn send_sync() {
+        assert_send_sync::<ConcurrentRingBuffer<String>>();
+    }
+}
@@ -1,9 +1,16 @@
 //! Please see the struct level documentation.
 
 use ccl_crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Pointer};
+#[cfg(feature = "serde")]
+use serde::ser::SerializeSeq;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::mem;
+use std::ops::Deref;
 use std::ptr;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub use crate::util::with_guard;
 
 /// Aquire a guard. These are needed when accessing a stack. Since aquiring a guard has a significant cost,
 /// you may wish to aquire a guard once and pass it around when doing bulk operations.
@@ -16,42 +23,116 @@ pub fn aquire_guard() -> Guard {
     epoch::pin()
 }
 
+/// The global epoch has to advance twice before garbage can be freed.
+const COLLECT_ROUNDS: usize = 3;
+
+/// Set in the reader count of a node once it has been popped.
+const POPPED: usize = 1 << (mem::size_of::<usize>() * 8 - 1);
+
 /// ConcurrentStack is a general purpose threadsafe and lockfree FILO/LIFO stack.
+///
+/// Memory is reclaimed using epoch based reclamation. The node of a popped element is not freed immediately
+/// but once no thread can still be holding a guard that may observe it. A thread keeping a guard alive for a long time
+/// therefore holds back reclamation for all threads. Reclamation is otherwise driven by normal stack operations,
+/// `collect` may be called to speed it up after popping a large amount of elements.
+///
+/// Reading an element in place, as `peek` does, registers as a reader on its node. A pop of that node waits
+/// for the readers to finish before moving the element out, so it is never dropped while it is being read.
+///
+/// The stack is Send and Sync when T is Send and Sync. Iterators hold an epoch guard and stay on their thread.
 pub struct ConcurrentStack<T> {
     head: Atomic<Node<T>>,
+    next_tag: AtomicUsize,
 }
 
 impl<T> Drop for ConcurrentStack<T> {
-    #[inline]
+    /// Frees the remaining nodes and drops their elements immediately.
+    /// No other thread can be accessing the stack anymore, so deferring is not needed.
     fn drop(&mut self) {
-        let guard = &aquire_guard();
-        let head = self.head.load(Ordering::SeqCst, guard);
+        unsafe {
+            let guard = epoch::unprotected();
+            let mut node_ptr = self.head.load(Ordering::Relaxed, guard);
 
-        if !head.is_null() {
-            unsafe {
-                guard.defer_destroy(head);
+            while !node_ptr.is_null() {
+                let node = node_ptr.into_owned().into_box();
+                node_ptr = node.next.load(Ordering::Relaxed, guard);
             }
         }
     }
 }
 
+/// The result of a single attempt at popping an element.
+#[derive(Debug, PartialEq)]
+pub enum PopResult<T> {
+    /// An element was popped.
+    Popped(T),
+
+    /// The stack was empty.
+    Empty,
+
+    /// The attempt lost a race with a concurrent operation on the stack.
+    Contended,
+}
+
 struct Node<T> {
     data: T,
+    tag: usize,
     next: Atomic<Node<T>>,
+    readers: AtomicUsize,
 }
 
-impl<T> Drop for Node<T> {
+impl<T> Node<T> {
     #[inline]
-    fn drop(&mut self) {
-        let guard = &aquire_guard();
-        let next = self.next.load(Ordering::SeqCst, guard);
+    fn new(data: T, tag: usize) -> Self {
+        Self {
+            data,
+            tag,
+            next: Atomic::null(),
+            readers: AtomicUsize::new(0),
+        }
+    }
 
-        if !next.is_null() {
-            unsafe {
-                guard.defer_destroy(next);
-            }
+    /// Register as a reader of the element. Returns `None` if the node has been popped already.
+    #[inline]
+    fn read(&self) -> Option<NodeRead<'_, T>> {
+        if self.readers.fetch_add(1, Ordering::SeqCst) & POPPED == 0 {
+            Some(NodeRead { node: self })
+        } else {
+            self.readers.fetch_sub(1, Ordering::SeqCst);
+            None
         }
     }
+
+    /// Mark the node as popped and wait until no reader is left, after which the element may be moved out.
+    #[inline]
+    fn mark_popped(&self) {
+        self.readers.fetch_or(POPPED, Ordering::SeqCst);
+
+        while self.readers.load(Ordering::SeqCst) != POPPED {
+            std::thread::yield_now();
+        }
+    }
+}
+
+/// A registered reader of a node, unregisters when dropped.
+struct NodeRead<'a, T> {
+    node: &'a Node<T>,
+}
+
+impl<'a, T> Deref for NodeRead<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.node.data
+    }
+}
+
+impl<'a, T> Drop for NodeRead<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.node.readers.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 impl<T> ConcurrentStack<T> {
@@ -59,6 +140,7 @@ impl<T> ConcurrentStack<T> {
     pub fn new() -> Self {
         Self {
             head: Atomic::null(),
+            next_tag: AtomicUsize::new(1),
         }
     }
 
@@ -76,6 +158,121 @@ impl<T> ConcurrentStack<T> {
         self.pop_with_guard(guard)
     }
 
+    /// Get a clone of the uppermost element of the stack without removing it.
+    #[inline]
+    pub fn peek(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        let guard = &aquire_guard();
+        self.peek_with_guard(guard)
+    }
+
+    /// Peek at the uppermost element with an existing guard.
+    /// A concurrent pop of the element waits until it has been cloned. If the element is popped before
+    /// it can be read the new uppermost element is tried instead.
+    #[inline]
+    pub fn peek_with_guard(&self, guard: &Guard) -> Option<T>
+    where
+        T: Clone,
+    {
+        loop {
+            let head = self.head.load(Ordering::SeqCst, guard);
+            let node = unsafe { head.as_ref() }?;
+
+            if let Some(data) = node.read() {
+                return Some((*data).clone());
+            }
+        }
+    }
+
+    /// Push every element of an iterator, pinning a single guard for all of them.
+    /// The last element ends up on top of the stack.
+    #[inline]
+    pub fn push_iter<I: IntoIterator<Item = T>>(&self, iter: I) {
+        let guard = &aquire_guard();
+
+        for data in iter {
+            self.push_with_guard(data, guard);
+        }
+    }
+
+    /// Pop up to `n` elements, pinning a single guard for all of them.
+    /// The elements are returned in pop order, fewer are returned if the stack runs empty.
+    #[inline]
+    pub fn pop_n(&self, n: usize) -> Vec<T> {
+        let guard = &aquire_guard();
+        let mut popped = Vec::with_capacity(n);
+
+        while popped.len() < n {
+            match self.pop_with_guard(guard) {
+                Some(data) => popped.push(data),
+                None => break,
+            }
+        }
+
+        popped
+    }
+
+    /// Pop and drop every element, pinning a single guard for all of them.
+    /// Elements pushed concurrently may or may not be removed.
+    #[inline]
+    pub fn clear(&self) {
+        let guard = &aquire_guard();
+        while self.pop_with_guard(guard).is_some() {}
+    }
+
+    /// Check if the stack is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        let guard = &aquire_guard();
+        self.head.load(Ordering::SeqCst, guard).is_null()
+    }
+
+    /// Try to advance the epoch and free the nodes of popped elements that are no longer reachable.
+    /// This is only a hint, memory observable by guards that are still alive is never freed.
+    #[inline]
+    pub fn collect(&self) {
+        for _ in 0..COLLECT_ROUNDS {
+            aquire_guard().flush();
+        }
+    }
+
+    /// Try to pop the uppermost element of the stack without retrying.
+    /// Returns `PopResult::Contended` if another thread modified the top of the stack during the attempt,
+    /// leaving it up to the caller to back off or try again.
+    #[inline]
+    pub fn try_pop(&self) -> PopResult<T> {
+        let guard = &aquire_guard();
+        self.try_pop_with_guard(guard)
+    }
+
+    /// Try to pop an element with an existing guard without retrying.
+    #[inline]
+    pub fn try_pop_with_guard(&self, guard: &Guard) -> PopResult<T> {
+        match self.try_pop_node(guard) {
+            PopResult::Popped((data, _)) => PopResult::Popped(data),
+            PopResult::Empty => PopResult::Empty,
+            PopResult::Contended => PopResult::Contended,
+        }
+    }
+
+    /// Push an element to the top of the stack and attach a unique tag to it.
+    /// Tags increase monotonically which lets callers detect that the stack changed even if
+    /// the same value ends up on top again. Elements pushed without a tag report a tag of 0.
+    #[inline]
+    pub fn push_tagged(&self, data: T) -> usize {
+        let guard = &aquire_guard();
+        self.push_tagged_with_guard(data, guard)
+    }
+
+    /// Pop the uppermost element of the stack together with its tag.
+    #[inline]
+    pub fn pop_tagged(&self) -> Option<(T, usize)> {
+        let guard = &aquire_guard();
+        self.pop_tagged_with_guard(guard)
+    }
+
     /// Create an iterator over all elements in the stack.
     #[inline]
     pub fn pop_iter(&self) -> StackIter<T> {
@@ -88,10 +285,20 @@ impl<T> ConcurrentStack<T> {
     /// Push an element with an existing guard.
     #[inline]
     pub fn push_with_guard(&self, data: T, guard: &Guard) {
-        let mut node = Owned::new(Node {
-            data,
-            next: Atomic::null(),
-        });
+        self.push_node(data, 0, guard);
+    }
+
+    /// Push a tagged element with an existing guard.
+    #[inline]
+    pub fn push_tagged_with_guard(&self, data: T, guard: &Guard) -> usize {
+        let tag = self.next_tag.fetch_add(1, Ordering::Relaxed);
+        self.push_node(data, tag, guard);
+        tag
+    }
+
+    #[inline]
+    fn push_node(&self, data: T, tag: usize, guard: &Guard) {
+        let mut node = Owned::new(Node::new(data, tag));
 
         loop {
             let head = self.head.load(Ordering::SeqCst, guard);
@@ -108,31 +315,67 @@ impl<T> ConcurrentStack<T> {
         }
     }
 
-    /// Pop an element with an existing guard.
+    /// Push an element without synchronizing with other threads.
+    ///
+    /// This skips the compare-and-swap loop entirely and is intended for bulk building a stack before it is shared.
+    /// The mutable borrow guarantees that no other thread can access the stack at the same time.
     #[inline]
-    pub fn pop_with_guard(&self, guard: &Guard) -> Option<T> {
-        loop {
-            let head_ptr = self.head.load(Ordering::SeqCst, guard);
+    pub fn push_unsync(&mut self, data: T) {
+        let guard = unsafe { epoch::unprotected() };
+        let node = Owned::new(Node::new(data, 0));
 
-            match unsafe { head_ptr.as_ref() } {
-                Some(head) => unsafe {
-                    let next = head.next.load(Ordering::SeqCst, guard);
+        node.next
+            .store(self.head.load(Ordering::Relaxed, guard), Ordering::Relaxed);
+        self.head.store(node, Ordering::Relaxed);
+    }
 
-                    if let Ok(head_ptr) =
-                        self.head
-                            .compare_and_set(head_ptr, next, Ordering::SeqCst, guard)
-                    {
-                        guard.defer_unchecked(move || {
-                            mem::drop(Box::from_raw(head_ptr.into_usize() as *mut u8));
-                        });
+    /// Pop an element with an existing guard.
+    #[inline]
+    pub fn pop_with_guard(&self, guard: &Guard) -> Option<T> {
+        self.pop_tagged_with_guard(guard).map(|(data, _)| data)
+    }
 
-                        return Some(ptr::read(&(*head).data));
-                    }
-                },
-                None => return None,
+    /// Pop an element together with its tag with an existing guard.
+    #[inline]
+    pub fn pop_tagged_with_guard(&self, guard: &Guard) -> Option<(T, usize)> {
+        loop {
+            match self.try_pop_node(guard) {
+                PopResult::Popped(popped) => return Some(popped),
+                PopResult::Empty => return None,
+                PopResult::Contended => {}
             }
         }
     }
+
+    #[inline]
+    fn try_pop_node(&self, guard: &Guard) -> PopResult<(T, usize)> {
+        let head_ptr = self.head.load(Ordering::SeqCst, guard);
+
+        match unsafe { head_ptr.as_ref() } {
+            Some(head) => unsafe {
+                let next = head.next.load(Ordering::SeqCst, guard);
+
+                if self
+                    .head
+                    .compare_and_set(head_ptr, next, Ordering::SeqCst, guard)
+                    .is_ok()
+                {
+                    // The data is moved out below so only the node allocation is freed.
+                    guard.defer_unchecked(move || {
+                        mem::drop(Box::from_raw(
+                            head_ptr.into_usize() as *mut mem::ManuallyDrop<Node<T>>
+                        ));
+                    });
+
+                    head.mark_popped();
+                    PopResult::Popped((ptr::read(&head.data), head.tag))
+                } else {
+                    PopResult::Contended
+                }
+            },
+            None => PopResult::Empty,
+        }
+    }
 }
 
 impl<T> Default for ConcurrentStack<T> {
@@ -141,6 +384,45 @@ impl<T> Default for ConcurrentStack<T> {
     }
 }
 
+/// Serializes the elements from top to bottom without removing them.
+///
+/// Every element is registered as read while it is serialized, so a concurrent pop of it waits until it is done.
+/// Elements popped before they are reached are skipped, the output is not a consistent snapshot if the stack
+/// is modified concurrently.
+#[cfg(feature = "serde")]
+impl<T: Serialize> Serialize for ConcurrentStack<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let guard = &aquire_guard();
+        let mut seq = serializer.serialize_seq(None)?;
+        let mut node_ptr = self.head.load(Ordering::SeqCst, guard);
+
+        while let Some(node) = unsafe { node_ptr.as_ref() } {
+            if let Some(data) = node.read() {
+                seq.serialize_element(&*data)?;
+            }
+
+            node_ptr = node.next.load(Ordering::SeqCst, guard);
+        }
+
+        seq.end()
+    }
+}
+
+/// Rebuilds a stack from elements ordered from top to bottom, preserving the pop order.
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for ConcurrentStack<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let elements = Vec::<T>::deserialize(deserializer)?;
+        let mut stack = Self::new();
+
+        for element in elements.into_iter().rev() {
+            stack.push_unsync(element);
+        }
+
+        Ok(stack)
+    }
+}
+
 /// An iterator over a stack.
 pub struct StackIter<'a, T> {
     guard: Guard,
@@ -159,6 +441,7 @@ impl<'a, T> Iterator for StackIter<'a, T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::assert_send_sync;
     use rayon::prelude::*;
 
     #[test]
@@ -174,6 +457,155 @@ mod tests {
         }
     }
 
+    #[test]
+    fn push_unsync_then_pop_rayon() {
+        let mut stack = ConcurrentStack::new();
+
+        for i in 0..1024_i32 {
+            stack.push_unsync(i);
+        }
+
+        assert_eq!(stack.pop(), Some(1023));
+
+        let sum: i32 = (0..1023_i32)
+            .into_par_iter()
+            .map(|_| stack.pop().unwrap())
+            .sum();
+
+        assert_eq!(sum, (0..1023).sum::<i32>());
+        assert!(stack.pop().is_none());
+    }
+
+    #[test]
+    fn tagged_detects_aba() {
+        let stack = ConcurrentStack::new();
+        stack.push(1);
+        let first = stack.push_tagged(9);
+
+        let (v, tag) = stack.pop_tagged().unwrap();
+        assert_eq!((v, tag), (9, first));
+
+        let second = stack.push_tagged(9);
+        let (v, tag) = stack.pop_tagged().unwrap();
+        assert_eq!(v, 9);
+        assert_eq!(tag, second);
+        assert!(second > first);
+
+        assert_eq!(stack.pop_tagged(), Some((1, 0)));
+    }
+
+    #[test]
+    fn try_pop_with_guard_uncontended() {
+        let stack = ConcurrentStack::new();
+        stack.push(1_i32);
+        stack.push(2);
+
+        let guard = &aquire_guard();
+        assert_eq!(stack.try_pop_with_guard(guard), PopResult::Popped(2));
+        assert_eq!(stack.try_pop_with_guard(guard), PopResult::Popped(1));
+        assert_eq!(stack.try_pop_with_guard(guard), PopResult::Empty);
+    }
+
+    #[test]
+    fn try_pop_contended_drains() {
+        let stack = ConcurrentStack::new();
+        assert_eq!(stack.try_pop(), PopResult::Empty);
+
+        let iter_c: i32 = 256 * 1024;
+
+        (0..iter_c).into_par_iter().for_each(|i| {
+            stack.push(i);
+        });
+
+        let mut popped = (0..8)
+            .into_par_iter()
+            .map(|_| {
+                let mut popped = Vec::new();
+
+                loop {
+                    match stack.try_pop() {
+                        PopResult::Popped(v) => popped.push(v),
+                        PopResult::Contended => {}
+                        PopResult::Empty => return popped,
+                    }
+                }
+            })
+            .reduce(Vec::new, |mut a, b| {
+                a.extend(b);
+                a
+            });
+
+        popped.sort();
+        assert_eq!(popped, (0..iter_c).collect::<Vec<_>>());
+        assert_eq!(stack.try_pop(), PopResult::Empty);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip_keeps_order() {
+        let stack = ConcurrentStack::new();
+
+        for i in 0..100_i32 {
+            stack.push(i);
+        }
+
+        let json = serde_json::to_string(&stack).unwrap();
+        let reloaded: ConcurrentStack<i32> = serde_json::from_str(&json).unwrap();
+
+        let expected: Vec<i32> = stack.pop_iter().collect();
+        let actual: Vec<i32> = reloaded.pop_iter().collect();
+        assert_eq!(expected.len(), 100);
+        assert_eq!(actual, expected);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_while_popping_rayon() {
+        let stack = ConcurrentStack::new();
+        stack.push_iter((0..10_000_u32).map(|i| i.to_string()));
+
+        (0..64_u32).into_par_iter().for_each(|i| {
+            if i % 2 == 0 {
+                let json = serde_json::to_string(&stack).unwrap();
+                let elements: Vec<String> = serde_json::from_str(&json).unwrap();
+                assert!(elements.iter().all(|e| e.parse::<u32>().unwrap() < 10_000));
+            } else {
+                stack.pop_n(400);
+            }
+        });
+
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn with_guard_reuses_pinned_guard() {
+        use crate::nestedmap::NestedMap;
+        use crate::util::GUARD_PINS;
+
+        let stack = ConcurrentStack::new();
+        let map = NestedMap::new();
+        let pins_before = GUARD_PINS.with(|pins| pins.get());
+
+        with_guard(|guard| {
+            for i in 0..1024_i32 {
+                with_guard(|nested| {
+                    stack.push_with_guard(i, nested);
+                    map.insert_with_guard(i, i * 2, nested);
+                });
+            }
+
+            assert_eq!(stack.pop_with_guard(guard), Some(1023));
+        });
+
+        assert_eq!(GUARD_PINS.with(|pins| pins.get()) - pins_before, 1);
+
+        with_guard(|_| ());
+        assert_eq!(GUARD_PINS.with(|pins| pins.get()) - pins_before, 2);
+
+        assert_eq!(stack.pop_iter().count(), 1023);
+        assert!((0..1024_i32).all(|i| *map.get(&i).unwrap() == i * 2));
+    }
+
     #[test]
     fn insert_then_pop_assert_rayon() {
         let stack = ConcurrentStack::new();
@@ -188,4 +620,122 @@ mod tests {
             assert_eq!(9, stack.pop().unwrap());
         });
     }
+
+    #[test]
+    fn push_iter_then_pop_n_rayon() {
+        let stack = ConcurrentStack::new();
+
+        (0..64_usize).into_par_iter().for_each(|i| {
+            stack.push_iter(i * 256..(i + 1) * 256);
+        });
+
+        let mut popped: Vec<usize> = (0..64)
+            .into_par_iter()
+            .flat_map_iter(|_| stack.pop_n(256))
+            .collect();
+
+        assert!(stack.pop_n(1).is_empty());
+        popped.sort();
+        assert_eq!(popped, (0..64 * 256).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn pop_n_order_and_short_stack() {
+        let stack = ConcurrentStack::new();
+        stack.push_iter(0..5_i32);
+
+        assert_eq!(stack.pop_n(3), vec![4, 3, 2]);
+        assert_eq!(stack.pop_n(3), vec![1, 0]);
+        assert!(stack.pop_n(3).is_empty());
+    }
+
+    #[test]
+    fn peek_while_popping_rayon() {
+        let stack = ConcurrentStack::new();
+        stack.push_iter((0..10_000_u32).map(|i| i.to_string()));
+
+        (0..20_000_u32).into_par_iter().for_each(|i| {
+            if i % 2 == 0 {
+                stack.pop();
+            } else if let Some(top) = stack.peek() {
+                assert!(top.parse::<u32>().unwrap() < 10_000);
+            }
+        });
+
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn peek_keeps_element() {
+        let stack = ConcurrentStack::new();
+        assert_eq!(stack.peek(), None);
+
+        stack.push_iter(vec!["foo".to_string(), "bar".to_string()]);
+
+        assert_eq!(stack.peek(), Some("bar".to_string()));
+        assert_eq!(
+            with_guard(|guard| stack.peek_with_guard(guard)),
+            Some("bar".to_string())
+        );
+        assert_eq!(stack.pop_n(3), vec!["bar", "foo"]);
+    }
+
+    #[test]
+    fn clear_drops_all_elements() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        struct Counted;
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let stack = ConcurrentStack::new();
+        assert!(stack.is_empty());
+
+        for _ in 0..1000 {
+            stack.push(Counted);
+        }
+
+        assert!(!stack.is_empty());
+        stack.clear();
+        assert!(stack.is_empty());
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 1000);
+
+        drop(stack);
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 1000);
+    }
+
+    #[test]
+    fn drop_frees_remaining_elements() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        struct Counted;
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let stack = ConcurrentStack::new();
+
+        for _ in 0..16 {
+            stack.push(Counted);
+        }
+
+        drop(stack.pop());
+        drop(stack.pop());
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 2);
+
+        drop(stack);
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 16);
+    }
+
+    #[test]
+    fn send_sync() {
+        assert_send_sync::<ConcurrentStack<String>>();
+    }
 }
@@ -5,31 +5,73 @@
 
 use crate::dashmap::DashMap;
 use parking_lot::Mutex;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
 use std::time;
 
 pub const VALID_DURATION: time::Duration = time::Duration::from_secs(3 * 60 * 60);
 pub const VALID_CHECK_INTERVAL: time::Duration = time::Duration::from_secs(30 * 60);
 pub const SAVE_INTERVAL: time::Duration = time::Duration::from_secs(3 * 60);
 
+/// A snapshot of the entries stored in a TimedCache.
+///
+/// Each entry is stored together with its age at the time of the snapshot, whether it was saved and its
+/// per-entry valid duration if one was set, so that expiry continues correctly when the state is loaded again.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+pub struct CacheState<K, V> {
+    entries: Vec<(K, V, time::Duration, bool, Option<time::Duration>)>,
+}
+
+/// Loads items missing from a TimedCache and saves modified ones.
+///
+/// Unlike plain functions an implementation can hold state such as a database handle.
+pub trait CacheBackend<K, V> {
+    /// Load the item stored under a key. Returns `None` if it does not exist.
+    fn load(&self, k: &K) -> Option<V>;
+
+    /// Save a modified item. Returns whether it was saved, unsaved items are tried again on the next save.
+    fn save(&self, k: &K, v: &V) -> bool;
+}
+
+/// Adapts the load and save functions passed to `TimedCache::new`.
+pub struct FnBackend<K, V> {
+    load_item: fn(&K) -> Option<V>,
+    save_item: fn(&K, &V) -> bool,
+}
+
+impl<K, V> CacheBackend<K, V> for FnBackend<K, V> {
+    fn load(&self, k: &K) -> Option<V> {
+        (self.load_item)(k)
+    }
+
+    fn save(&self, k: &K, v: &V) -> bool {
+        (self.save_item)(k, v)
+    }
+}
+
 /// Threadsafe concurrent timed cache.
 /// Handles loading and potential saving behind the scenes with user supplied functions.
 /// Intended for use in high concurrency applications.
 ///
 /// The `do_check` method has to be called periodically to do maintenance.
 /// The supplied durations ensure that maintenance is done independently of how often `do_check` is called.
-pub struct TimedCache<K, V>
+pub struct TimedCache<K, V, B = FnBackend<K, V>>
 where
     K: Hash + Eq + Clone,
 {
-    storage: DashMap<K, (V, time::Instant, bool)>,
-    load_item_fn: fn(&K) -> Option<V>,
-    save_item_fn: fn(&K, &V) -> bool,
+    storage: DashMap<K, (V, time::Instant, bool, Option<time::Duration>)>,
+    backend: B,
     last_saved: Mutex<time::Instant>,
     last_purged: Mutex<time::Instant>,
     valid_duration: time::Duration,
     valid_check_interval: time::Duration,
     save_interval: time::Duration,
+    maintenance_generation: AtomicUsize,
 }
 
 impl<'a, K: Hash + Eq + Clone, V> TimedCache<K, V> {
@@ -47,50 +89,188 @@ impl<'a, K: Hash + Eq + Clone, V> TimedCache<K, V> {
         valid_duration: Option<time::Duration>,
         valid_check_interval: Option<time::Duration>,
         save_interval: Option<time::Duration>,
+    ) -> Self {
+        Self::with_backend(
+            FnBackend {
+                load_item,
+                save_item,
+            },
+            valid_duration,
+            valid_check_interval,
+            save_interval,
+        )
+    }
+}
+
+impl<K: Hash + Eq + Clone, V, B: CacheBackend<K, V>> TimedCache<K, V, B> {
+    /// Same as `new` but loads and saves items through a backend instead of plain functions.
+    pub fn with_backend(
+        backend: B,
+        valid_duration: Option<time::Duration>,
+        valid_check_interval: Option<time::Duration>,
+        save_interval: Option<time::Duration>,
     ) -> Self {
         Self {
             storage: DashMap::default(),
-            load_item_fn: load_item,
-            save_item_fn: save_item,
+            backend,
             last_saved: Mutex::new(time::Instant::now()),
             last_purged: Mutex::new(time::Instant::now()),
             valid_duration: valid_duration.unwrap_or(VALID_DURATION),
             valid_check_interval: valid_check_interval.unwrap_or(VALID_CHECK_INTERVAL),
             save_interval: save_interval.unwrap_or(SAVE_INTERVAL),
+            maintenance_generation: AtomicUsize::new(0),
         }
     }
 
     /// Load an item with a specified key. Intended to mainly be called from `map` and `map_mut`
     pub fn load_item(&self, k: &K) {
         if !self.storage.contains_key(k) {
-            if let Some(v) = (self.load_item_fn)(k) {
-                let v = (v, time::Instant::now(), true);
+            if let Some(v) = self.backend.load(k) {
+                let v = (v, time::Instant::now(), true, None);
                 self.storage.insert(k.clone(), v);
             }
         }
     }
 
+    /// Load a set of items up front instead of lazily on first access. Items already cached are not loaded again.
+    pub fn warm<I: IntoIterator<Item = K>>(&self, keys: I) {
+        keys.into_iter().for_each(|k| self.load_item(&k));
+    }
+
     /// Takes a closure with a normal reference as an argument and executes it.
     /// The function will return the same value as the closure which means the function can be used to extract data.
+    ///
+    /// Will panic if the item can't be loaded, see `try_map` for a non panicking version.
     pub fn map<T, F: FnOnce(&V) -> T>(&self, k: &K, f: F) -> T {
-        self.load_item(k);
-        let data = self.storage.get(k).unwrap();
-        f(&data.0)
+        self.try_map(k, f).expect("TimedCache failed to load item")
     }
 
     /// Takes a closure with a mutable reference as an argument and executes it.
     /// The function will return the same value as the closure which means the function can be used to extract data.
+    ///
+    /// Will panic if the item can't be loaded, see `try_map_mut` for a non panicking version.
     pub fn map_mut<T, F: FnOnce(&mut V) -> T>(&self, k: &K, f: F) -> T {
+        self.try_map_mut(k, f)
+            .expect("TimedCache failed to load item")
+    }
+
+    /// Same as `map_mut` but also sets how long this entry is valid, overriding the valid duration of the cache.
+    /// The override stays until the entry is evicted.
+    pub fn map_mut_with_ttl<T, F: FnOnce(&mut V) -> T>(
+        &self,
+        k: &K,
+        ttl: time::Duration,
+        f: F,
+    ) -> T {
         self.load_item(k);
-        let mut data = self.storage.get_mut(k).unwrap();
+        let mut data = self
+            .storage
+            .get_mut(k)
+            .expect("TimedCache failed to load item");
         data.2 = false;
+        data.3 = Some(ttl);
         f(&mut data.0)
     }
 
+    /// Same as `map` but returns `None` if the item isn't cached and the load function returns `None`.
+    pub fn try_map<T, F: FnOnce(&V) -> T>(&self, k: &K, f: F) -> Option<T> {
+        self.load_item(k);
+        self.storage.get(k).map(|data| f(&data.0))
+    }
+
+    /// Same as `map_mut` but returns `None` if the item isn't cached and the load function returns `None`.
+    pub fn try_map_mut<T, F: FnOnce(&mut V) -> T>(&self, k: &K, f: F) -> Option<T> {
+        self.load_item(k);
+        self.storage.get_mut(k).map(|mut data| {
+            data.2 = false;
+            f(&mut data.0)
+        })
+    }
+
+    /// Evict an entry immediately without saving it. Returns whether the entry was cached.
+    /// The next access loads it again.
+    pub fn invalidate(&self, k: &K) -> bool {
+        self.storage.remove(k).is_some()
+    }
+
+    /// Evict all entries immediately without saving them.
+    pub fn invalidate_all(&self) {
+        self.storage.clear();
+    }
+
+    /// Read an entry if it is currently cached, without loading it or marking it as modified.
+    pub fn peek<T, F: FnOnce(&V) -> T>(&self, k: &K, f: F) -> Option<T> {
+        self.storage.get(k).map(|data| f(&data.0))
+    }
+
+    /// Check if an entry is currently cached without loading it.
+    pub fn contains(&self, k: &K) -> bool {
+        self.storage.contains_key(k)
+    }
+
+    /// Get the time elapsed since an entry was loaded without loading it.
+    /// Returns `None` if the entry is not cached.
+    pub fn age(&self, k: &K) -> Option<time::Duration> {
+        let now = time::Instant::now();
+
+        self.storage
+            .get(k)
+            .map(|data| now.saturating_duration_since(data.1))
+    }
+
+    /// Collects all entries that were loaded after the given instant.
+    /// Useful for incremental syncing of recent activity.
+    ///
+    /// Modifying an entry does not refresh its timestamp since that would also postpone its expiry,
+    /// so entries modified after the instant are only collected if they were loaded after it too.
+    pub fn modified_since(&self, since: time::Instant) -> Vec<(K, V)>
+    where
+        V: Clone,
+    {
+        self.storage
+            .iter()
+            .filter(|r| r.value().1 > since)
+            .map(|r| (r.key().clone(), r.value().0.clone()))
+            .collect()
+    }
+
+    /// Takes a snapshot of all entries without invoking the load or save functions.
+    #[cfg(feature = "serde")]
+    pub fn serialize_state(&self) -> CacheState<K, V>
+    where
+        V: Clone,
+    {
+        let now = time::Instant::now();
+
+        let entries = self
+            .storage
+            .iter()
+            .map(|r| {
+                let (v, timestamp, saved, ttl) = r.value();
+                let age = now.saturating_duration_since(*timestamp);
+                (r.key().clone(), v.clone(), age, *saved, *ttl)
+            })
+            .collect();
+
+        CacheState { entries }
+    }
+
+    /// Loads a snapshot taken with `serialize_state` into the cache without invoking the load or save functions.
+    /// Entries keep their age so that they expire on the same schedule as before.
+    #[cfg(feature = "serde")]
+    pub fn load_state(&self, state: CacheState<K, V>) {
+        let now = time::Instant::now();
+
+        for (k, v, age, saved, ttl) in state.entries {
+            let timestamp = now.checked_sub(age).unwrap_or(now);
+            self.storage.insert(k, (v, timestamp, saved, ttl));
+        }
+    }
+
     /// Saves all entries. Useful to run before shutting down gracefully.
     pub fn save_all(&self) {
-        let check_save_item = |k: &K, v: &mut (V, time::Instant, bool)| {
-            if !v.2 && (self.save_item_fn)(k, &v.0) {
+        let check_save_item = |k: &K, v: &mut (V, time::Instant, bool, Option<time::Duration>)| {
+            if !v.2 && self.backend.save(k, &v.0) {
                 v.2 = true;
             }
         };
@@ -102,6 +282,37 @@ impl<'a, K: Hash + Eq + Clone, V> TimedCache<K, V> {
         });
     }
 
+    /// Spawn a thread calling `do_check` every `interval`.
+    /// The thread only holds a weak reference and exits once the cache is dropped or `stop_maintenance` is called.
+    pub fn spawn_maintenance(self: Arc<Self>, interval: time::Duration) -> JoinHandle<()>
+    where
+        K: Send + Sync + 'static,
+        V: Send + Sync + 'static,
+        B: Send + Sync + 'static,
+    {
+        // Threads exit once the generation they were spawned in has been stopped.
+        let generation = self.maintenance_generation.load(Ordering::SeqCst);
+        let cache = Arc::downgrade(&self);
+
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+
+            match cache.upgrade() {
+                Some(cache)
+                    if cache.maintenance_generation.load(Ordering::SeqCst) == generation =>
+                {
+                    cache.do_check()
+                }
+                _ => return,
+            }
+        })
+    }
+
+    /// Make maintenance threads started with `spawn_maintenance` exit after their current wait.
+    pub fn stop_maintenance(&self) {
+        self.maintenance_generation.fetch_add(1, Ordering::SeqCst);
+    }
+
     /// Performs maintenance tasks like saving and evicting invalid entries.
     /// May take significant time depending on amount of entries and the time complexity of saving each.
     /// This is intended to be improved in a future iteration of TimedCache.
@@ -110,15 +321,16 @@ impl<'a, K: Hash + Eq + Clone, V> TimedCache<K, V> {
         let mut last_saved = self.last_saved.lock();
         let mut last_purged = self.last_purged.lock();
 
-        let check_save_item = |k: &K, v: &mut (V, time::Instant, bool)| {
-            if !v.2 && (self.save_item_fn)(k, &v.0) {
+        let check_save_item = |k: &K, v: &mut (V, time::Instant, bool, Option<time::Duration>)| {
+            if !v.2 && self.backend.save(k, &v.0) {
                 v.2 = true;
             }
         };
 
-        let check_to_evict = |_k: &K, v: &mut (V, time::Instant, bool)| -> bool {
-            now.duration_since(v.1) > self.valid_duration && v.2
-        };
+        let check_to_evict =
+            |_k: &K, v: &mut (V, time::Instant, bool, Option<time::Duration>)| -> bool {
+                now.duration_since(v.1) > v.3.unwrap_or(self.valid_duration) && v.2
+            };
 
         if now.duration_since(*last_saved) > self.save_interval {
             *last_saved = now;
@@ -137,3 +349,343 @@ impl<'a, K: Hash + Eq + Clone, V> TimedCache<K, V> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::assert_send_sync;
+    use std::sync::atomic::AtomicUsize;
+
+    fn load_item(k: &u32) -> Option<u32> {
+        Some(k * 2)
+    }
+
+    fn save_item(_k: &u32, _v: &u32) -> bool {
+        true
+    }
+
+    #[test]
+    fn modified_since_staggered() {
+        let cache = TimedCache::new(load_item, save_item, None, None, None);
+
+        for i in 0..8_u32 {
+            cache.map(&i, |_| ());
+        }
+
+        thread::sleep(time::Duration::from_millis(10));
+        let since = time::Instant::now();
+        thread::sleep(time::Duration::from_millis(10));
+
+        for i in 8..12_u32 {
+            cache.map(&i, |_| ());
+        }
+
+        let mut recent = cache.modified_since(since);
+        recent.sort();
+        assert_eq!(recent, vec![(8, 16), (9, 18), (10, 20), (11, 22)]);
+    }
+
+    #[test]
+    fn modified_since_ignores_mutation() {
+        let cache = TimedCache::new(load_item, save_item, None, None, None);
+        cache.map(&1_u32, |_| ());
+
+        thread::sleep(time::Duration::from_millis(10));
+        let since = time::Instant::now();
+
+        cache.map_mut(&1, |v| *v += 1);
+        cache.map(&2, |_| ());
+
+        assert_eq!(cache.modified_since(since), vec![(2, 4)]);
+        assert!(cache.age(&1).unwrap() >= time::Duration::from_millis(10));
+    }
+
+    fn load_len(k: &&str) -> Option<usize> {
+        Some(k.len())
+    }
+
+    fn save_len(_k: &&str, _v: &usize) -> bool {
+        true
+    }
+
+    #[test]
+    fn new_with_borrowed_keys() {
+        let key = String::from("foo");
+        let cache = TimedCache::new(load_len, save_len, None, None, None);
+
+        assert_eq!(cache.map(&key.as_str(), |v| *v), 3);
+    }
+
+    static WARMED: std::sync::Mutex<Vec<u32>> = std::sync::Mutex::new(Vec::new());
+
+    fn load_item_recorded(k: &u32) -> Option<u32> {
+        WARMED.lock().unwrap().push(*k);
+        Some(k * 2)
+    }
+
+    #[test]
+    fn warm_loads_all() {
+        let cache = TimedCache::new(load_item_recorded, save_item, None, None, None);
+        cache.warm(0..16_u32);
+        cache.warm(vec![3, 7]);
+
+        let mut warmed = WARMED.lock().unwrap().clone();
+        warmed.sort();
+        assert_eq!(warmed, (0..16).collect::<Vec<_>>());
+        assert!((0..16).all(|k| cache.contains(&k)));
+        assert!(!cache.contains(&16));
+    }
+
+    #[test]
+    fn contains_and_age_do_not_load() {
+        let cache = TimedCache::new(load_item, save_item, None, None, None);
+
+        assert!(!cache.contains(&1));
+        assert_eq!(cache.age(&1), None);
+        assert!(!cache.contains(&1));
+
+        cache.map(&1, |_| ());
+        thread::sleep(time::Duration::from_millis(20));
+
+        assert!(cache.contains(&1));
+        let age = cache.age(&1).unwrap();
+        assert!(age >= time::Duration::from_millis(20));
+        assert!(age < time::Duration::from_secs(1));
+    }
+
+    fn load_odd(k: &u32) -> Option<u32> {
+        if k % 2 == 1 {
+            Some(k * 2)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn try_map_missing_item() {
+        let cache = TimedCache::new(load_odd, save_item, None, None, None);
+
+        assert_eq!(cache.try_map(&1, |v| *v), Some(2));
+        assert_eq!(cache.try_map(&2, |v| *v), None);
+        assert_eq!(cache.try_map_mut(&2, |v| *v += 1), None);
+        assert_eq!(cache.try_map_mut(&3, |v| *v += 1), Some(()));
+        assert_eq!(cache.map(&3, |v| *v), 7);
+        assert!(!cache.contains(&2));
+    }
+
+    static LOADS: AtomicUsize = AtomicUsize::new(0);
+
+    fn load_item_counted(k: &u32) -> Option<u32> {
+        LOADS.fetch_add(1, Ordering::SeqCst);
+        Some(k * 2)
+    }
+
+    #[test]
+    fn invalidate_reloads() {
+        let cache = TimedCache::new(load_item_counted, save_item, None, None, None);
+
+        cache.map_mut(&1, |v| *v = 0);
+        cache.map(&2, |_| ());
+        assert_eq!(LOADS.load(Ordering::SeqCst), 2);
+
+        assert!(cache.invalidate(&1));
+        assert!(!cache.invalidate(&1));
+        assert_eq!(cache.map(&1, |v| *v), 2);
+        assert_eq!(LOADS.load(Ordering::SeqCst), 3);
+
+        cache.invalidate_all();
+        assert!(!cache.contains(&1) && !cache.contains(&2));
+        assert_eq!(cache.map(&2, |v| *v), 4);
+        assert_eq!(LOADS.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn per_entry_ttl_evicts_early() {
+        let cache = TimedCache::new(
+            load_item,
+            save_item,
+            Some(time::Duration::from_secs(60 * 60)),
+            Some(time::Duration::from_millis(1)),
+            Some(time::Duration::from_millis(1)),
+        );
+
+        cache.map_mut_with_ttl(&1, time::Duration::from_millis(10), |v| *v += 1);
+        cache.map_mut(&2, |v| *v += 1);
+
+        thread::sleep(time::Duration::from_millis(30));
+        cache.do_check();
+
+        assert!(!cache.contains(&1));
+        assert!(cache.contains(&2));
+    }
+
+    #[derive(Default)]
+    struct CountingBackend {
+        loads: Arc<AtomicUsize>,
+        saves: Arc<AtomicUsize>,
+    }
+
+    impl CacheBackend<u32, u32> for CountingBackend {
+        fn load(&self, k: &u32) -> Option<u32> {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            Some(k * 2)
+        }
+
+        fn save(&self, _k: &u32, _v: &u32) -> bool {
+            self.saves.fetch_add(1, Ordering::SeqCst);
+            true
+        }
+    }
+
+    #[test]
+    fn backend_counts_loads_and_saves() {
+        let backend = CountingBackend::default();
+        let loads = backend.loads.clone();
+        let saves = backend.saves.clone();
+        let cache = TimedCache::with_backend(backend, None, None, None);
+
+        cache.map(&1, |_| ());
+        cache.map(&1, |_| ());
+        cache.map_mut(&2, |v| *v += 1);
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+
+        cache.save_all();
+        cache.save_all();
+        assert_eq!(saves.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.map(&2, |v| *v), 5);
+    }
+
+    static SAVED: std::sync::Mutex<Vec<u32>> = std::sync::Mutex::new(Vec::new());
+
+    fn save_item_recorded(k: &u32, _v: &u32) -> bool {
+        SAVED.lock().unwrap().push(*k);
+        true
+    }
+
+    #[test]
+    fn maintenance_thread_saves() {
+        let cache = Arc::new(TimedCache::new(
+            load_item,
+            save_item_recorded,
+            None,
+            None,
+            Some(time::Duration::from_millis(1)),
+        ));
+
+        for i in 0..4_u32 {
+            cache.map_mut(&i, |v| *v += 1);
+        }
+
+        let handle = cache
+            .clone()
+            .spawn_maintenance(time::Duration::from_millis(5));
+
+        for _ in 0..200 {
+            if SAVED.lock().unwrap().len() >= 4 {
+                break;
+            }
+
+            thread::sleep(time::Duration::from_millis(5));
+        }
+
+        cache.stop_maintenance();
+        handle.join().unwrap();
+
+        let mut saved = SAVED.lock().unwrap().clone();
+        saved.sort();
+        assert_eq!(saved, vec![0, 1, 2, 3]);
+
+        let handle = cache
+            .clone()
+            .spawn_maintenance(time::Duration::from_millis(5));
+        drop(cache);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn stopped_maintenance_stays_stopped() {
+        let cache = Arc::new(TimedCache::new(load_item, save_item, None, None, None));
+
+        let stopped = cache
+            .clone()
+            .spawn_maintenance(time::Duration::from_millis(50));
+        cache.stop_maintenance();
+
+        // Respawning before the stopped thread woke up must not revive it.
+        let running = cache
+            .clone()
+            .spawn_maintenance(time::Duration::from_millis(5));
+        stopped.join().unwrap();
+
+        assert!(!running.is_finished());
+        cache.stop_maintenance();
+        running.join().unwrap();
+    }
+
+    static PEEK_SAVES: AtomicUsize = AtomicUsize::new(0);
+
+    fn save_item_peek(_k: &u32, _v: &u32) -> bool {
+        PEEK_SAVES.fetch_add(1, Ordering::SeqCst);
+        true
+    }
+
+    #[test]
+    fn peek_does_not_dirty() {
+        let cache = TimedCache::new(
+            load_item,
+            save_item_peek,
+            None,
+            None,
+            Some(time::Duration::from_millis(1)),
+        );
+
+        assert_eq!(cache.peek(&1, |v| *v), None);
+        assert!(!cache.contains(&1));
+
+        cache.map(&1, |_| ());
+        assert_eq!(cache.peek(&1, |v| *v), Some(2));
+
+        thread::sleep(time::Duration::from_millis(5));
+        cache.do_check();
+        assert_eq!(PEEK_SAVES.load(Ordering::SeqCst), 0);
+
+        cache.map_mut(&1, |v| *v += 1);
+        thread::sleep(time::Duration::from_millis(5));
+        cache.do_check();
+        assert_eq!(PEEK_SAVES.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn state_round_trip() {
+        let cache = TimedCache::new(load_item, save_item, None, None, None);
+
+        for i in 0..16_u32 {
+            cache.map(&i, |_| ());
+        }
+
+        thread::sleep(time::Duration::from_millis(20));
+        let state = cache.serialize_state();
+        let json = serde_json::to_string(&state).unwrap();
+
+        let reloaded = TimedCache::new(load_item, save_item, None, None, None);
+        reloaded.load_state(serde_json::from_str(&json).unwrap());
+
+        let mut before = state.entries;
+        let mut after = reloaded.serialize_state().entries;
+        before.sort_by_key(|e| e.0);
+        after.sort_by_key(|e| e.0);
+
+        assert_eq!(after.len(), 16);
+        for (b, a) in before.iter().zip(after.iter()) {
+            assert_eq!((b.0, b.1, b.3), (a.0, a.1, a.3));
+            assert!(a.2 >= b.2 && a.2 >= time::Duration::from_millis(20));
+            assert!(a.2 < b.2 + time::Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn send_sync() {
+        assert_send_sync::<TimedCache<String, Vec<u8>>>();
+    }
+}
@@ -1,33 +1,81 @@
 //! This is a thin allocator wrapper for use in some datastructures.
-//! This is here incase we want to implement a custom allocator at a later date.
+//! Freed blocks are kept on a free list per tag and handed out again by later allocations with the same tag.
+//! The free lists are intrusive, a freed block stores the address of the next free block in its first word
+//! so recycling a block never calls the global allocator.
 
+use parking_lot::Mutex;
 use std::alloc::{alloc, dealloc, Layout};
 use std::marker::PhantomData;
+use std::mem;
 use std::ptr;
 
+/// Amount of free lists, tags are mapped onto them by their value modulo this.
+const TAG_COUNT: usize = 256;
+
 /// Please see module level documentation.
 pub struct UniformAllocator<T> {
+    /// Address of the first free block per tag, zero if the list is empty.
+    free: Box<[Mutex<usize>]>,
     marker: PhantomData<T>,
 }
 
 impl<T> UniformAllocator<T> {
+    const BLOCK_HOLDS_LINK: () = assert!(
+        mem::size_of::<T>() >= mem::size_of::<usize>(),
+        "blocks must be large enough to hold a free list link"
+    );
+
     pub fn new() -> Self {
+        let () = Self::BLOCK_HOLDS_LINK;
+
         Self {
+            free: (0..TAG_COUNT)
+                .map(|_| Mutex::new(0))
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
             marker: PhantomData,
         }
     }
 
     #[inline(always)]
-    pub fn alloc(&self, _tag: usize) -> *mut u8 {
-        unsafe { alloc(Layout::new::<T>()) }
+    pub fn alloc(&self, tag: usize) -> *mut u8 {
+        let mut head = self.free[tag % TAG_COUNT].lock();
+
+        match *head {
+            0 => {
+                drop(head);
+                unsafe { alloc(Layout::new::<T>()) }
+            }
+            ptr => {
+                *head = unsafe { ptr::read_unaligned(ptr as *const usize) };
+                ptr as *mut u8
+            }
+        }
     }
 
+    /// Move the value out of a block and put the block on the free list of its tag.
     #[inline(always)]
-    pub fn dealloc(&self, _tag: usize, ptr: *mut u8) -> Option<T> {
-        unsafe {
-            let data = ptr::read(ptr as *const _);
-            dealloc(ptr, Layout::new::<T>());
-            data
+    pub fn dealloc(&self, tag: usize, ptr: *mut u8) -> Option<T> {
+        let data = unsafe { ptr::read(ptr as *const T) };
+        let mut head = self.free[tag % TAG_COUNT].lock();
+
+        unsafe { ptr::write_unaligned(ptr as *mut usize, *head) };
+        *head = ptr as usize;
+
+        Some(data)
+    }
+}
+
+impl<T> Drop for UniformAllocator<T> {
+    fn drop(&mut self) {
+        for head in self.free.iter_mut() {
+            let mut ptr = *head.get_mut();
+
+            while ptr != 0 {
+                let next = unsafe { ptr::read_unaligned(ptr as *const usize) };
+                unsafe { dealloc(ptr as *mut u8, Layout::new::<T>()) }
+                ptr = next;
+            }
         }
     }
 }
@@ -37,3 +85,64 @@ impl<T> Default for UniformAllocator<T> {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rayon::prelude::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn freed_blocks_are_reused() {
+        let allocator = UniformAllocator::<[u64; 4]>::new();
+
+        let first: HashSet<usize> = (0..64)
+            .map(|_| {
+                let ptr = allocator.alloc(7);
+                unsafe { ptr::write(ptr as *mut [u64; 4], [1; 4]) };
+                ptr as usize
+            })
+            .collect();
+
+        for ptr in first.iter() {
+            assert_eq!(allocator.dealloc(7, *ptr as *mut u8), Some([1; 4]));
+        }
+
+        let second: HashSet<usize> = (0..64).map(|_| allocator.alloc(7) as usize).collect();
+        assert_eq!(first, second);
+
+        for ptr in second {
+            unsafe { ptr::write(ptr as *mut [u64; 4], [0; 4]) };
+            allocator.dealloc(7, ptr as *mut u8);
+        }
+    }
+
+    #[test]
+    fn alloc_dealloc_rayon() {
+        let allocator = UniformAllocator::<String>::new();
+
+        (0..100_000_usize).into_par_iter().for_each(|i| {
+            let tag = i % 16;
+            let ptr = allocator.alloc(tag);
+            unsafe { ptr::write(ptr as *mut String, i.to_string()) };
+            assert_eq!(allocator.dealloc(tag, ptr), Some(i.to_string()));
+        });
+
+        let pooled: usize = allocator
+            .free
+            .iter()
+            .map(|head| {
+                let mut ptr = *head.lock();
+                let mut count = 0;
+
+                while ptr != 0 {
+                    ptr = unsafe { ptr::read_unaligned(ptr as *const usize) };
+                    count += 1;
+                }
+
+                count
+            })
+            .sum();
+        assert!(pooled < 100_000);
+    }
+}
@@ -1,9 +1,12 @@
 use crate::uniform_allocator::UniformAllocator;
-use ccl_crossbeam_epoch::{self as epoch, Atomic, Owned, Pointer, Shared};
+use ccl_crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Pointer, Shared};
+use std::cell::Cell;
 use std::hash::{Hash, Hasher};
+use std::hint;
 use std::mem;
 use std::ptr;
 use std::sync::atomic::Ordering;
+use std::thread;
 
 pub trait UniformAllocExt<T> {
     fn uniform_alloc(allocator: &UniformAllocator<T>, tag: usize, v: T) -> Self;
@@ -101,3 +104,67 @@ pub const fn ptr_size_bits() -> usize {
 pub unsafe fn map_in_place<T>(r: &mut T, f: impl FnOnce(T) -> T) {
     ptr::write(r, f(ptr::read(r)));
 }
+
+/// Back off for an exponentially growing amount of time based on the attempt number.
+/// Spins for the first few attempts and yields to the scheduler after that.
+#[inline]
+pub fn backoff(step: u32) {
+    const SPIN_LIMIT: u32 = 6;
+
+    if step <= SPIN_LIMIT {
+        for _ in 0..1 << step {
+            hint::spin_loop();
+        }
+    } else {
+        thread::yield_now();
+    }
+}
+
+thread_local! {
+    static CURRENT_GUARD: Cell<*const Guard> = const { Cell::new(ptr::null()) };
+}
+
+#[cfg(test)]
+thread_local! {
+    pub static GUARD_PINS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Restores the previous thread local guard when a `with_guard` scope ends, even on panic.
+struct GuardScope(*const Guard);
+
+impl Drop for GuardScope {
+    #[inline]
+    fn drop(&mut self) {
+        CURRENT_GUARD.with(|current| current.set(self.0));
+    }
+}
+
+/// Run a function with a pinned guard. Nested calls on the same thread reuse the guard pinned by the outermost call
+/// instead of pinning a new one.
+///
+/// Please note that no memory removed while the outermost guard is alive can be reclaimed until it is dropped,
+/// so avoid wrapping long running work in a single scope.
+#[inline]
+pub fn with_guard<R>(f: impl FnOnce(&Guard) -> R) -> R {
+    let current = CURRENT_GUARD.with(|current| current.get());
+
+    if !current.is_null() {
+        // The pointer is only set while the outer scope keeps the guard alive on this thread.
+        return f(unsafe { &*current });
+    }
+
+    #[cfg(test)]
+    GUARD_PINS.with(|pins| pins.set(pins.get() + 1));
+
+    let guard = epoch::pin();
+    CURRENT_GUARD.with(|current| current.set(&guard));
+    let _scope = GuardScope(ptr::null());
+
+    f(&guard)
+}
+
+#[cfg(test)]
+pub fn assert_send_sync<T: Send + Sync>() {}
+
+#[cfg(test)]
+pub fn assert_sync<T: Sync>() {}
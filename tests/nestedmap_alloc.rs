@@ -0,0 +1,47 @@
+//! Checks that recycling buckets in NestedMap does not go through the global allocator.
+//! Lives in its own test binary so the counting allocator doesn't apply to other tests.
+
+use ccl::nestedmap::NestedMap;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts every call to the global allocator.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+#[test]
+fn bucket_churn_stops_allocating() {
+    let map = NestedMap::new();
+    map.insert(1_u64, 1_u64);
+
+    // An existing key makes `get_or_insert` free its freshly allocated bucket right away.
+    let churn = |rounds| {
+        for i in 0..rounds {
+            assert_eq!(*map.get_or_insert(1, i), 1);
+        }
+    };
+
+    // Fill the free list of every tag.
+    churn(10_000);
+
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    churn(100_000);
+    let after = ALLOCATIONS.load(Ordering::SeqCst);
+
+    assert!(after - before < 16);
+}